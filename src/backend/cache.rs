@@ -0,0 +1,116 @@
+use super::Backend;
+use crate::error::Error;
+use crate::tree::Con;
+use i3ipc::reply::{
+    Output as I3Output, Outputs as I3Outputs, Workspace as I3Workspace, Workspaces as I3Workspaces,
+};
+use serde_json::Value;
+
+/// Keeps the last replies of another backend in memory so that repeated
+/// queries don't go back to the window manager.
+///
+/// Running a command drops them, since it most likely changed the layout.
+/// The daemon also drops and refetches them whenever i3 reports an event.
+pub struct CachedBackend {
+    backend: Box<dyn Backend>,
+    tree: Option<Con>,
+    workspaces: Option<I3Workspaces>,
+    outputs: Option<I3Outputs>,
+}
+
+impl CachedBackend {
+    /// Caches the replies of `backend`, starting out empty.
+    pub fn new(backend: Box<dyn Backend>) -> Self {
+        CachedBackend {
+            backend,
+            tree: None,
+            workspaces: None,
+            outputs: None,
+        }
+    }
+
+    /// Drops the cached replies, so that the next queries fetch them.
+    pub fn invalidate(&mut self) {
+        self.tree = None;
+        self.workspaces = None;
+        self.outputs = None;
+    }
+
+    /// Fetches everything again, so that the next queries are answered
+    /// from memory.
+    pub fn refresh(&mut self) -> Result<(), Error> {
+        self.invalidate();
+        self.get_tree()?;
+        self.get_workspaces()?;
+        self.get_outputs()?;
+        Ok(())
+    }
+}
+
+impl Backend for CachedBackend {
+    fn get_tree(&mut self) -> Result<Con, Error> {
+        if self.tree.is_none() {
+            self.tree = Some(self.backend.get_tree()?);
+        }
+        Ok(self.tree.clone().unwrap())
+    }
+
+    fn get_workspaces(&mut self) -> Result<I3Workspaces, Error> {
+        if self.workspaces.is_none() {
+            self.workspaces = Some(self.backend.get_workspaces()?);
+        }
+        Ok(copy_workspaces(self.workspaces.as_ref().unwrap()))
+    }
+
+    fn get_outputs(&mut self) -> Result<I3Outputs, Error> {
+        if self.outputs.is_none() {
+            self.outputs = Some(self.backend.get_outputs()?);
+        }
+        Ok(copy_outputs(self.outputs.as_ref().unwrap()))
+    }
+
+    fn get_snapshot(&mut self) -> Result<Value, Error> {
+        self.backend.get_snapshot()
+    }
+
+    fn run_command(&mut self, command: &str) -> Result<(), Error> {
+        self.invalidate();
+        self.backend.run_command(command)
+    }
+}
+
+// i3ipc's reply types don't implement `Clone`.
+
+fn copy_workspaces(workspaces: &I3Workspaces) -> I3Workspaces {
+    I3Workspaces {
+        workspaces: workspaces
+            .workspaces
+            .iter()
+            .map(|workspace| I3Workspace {
+                num: workspace.num,
+                name: workspace.name.clone(),
+                visible: workspace.visible,
+                focused: workspace.focused,
+                urgent: workspace.urgent,
+                rect: workspace.rect,
+                output: workspace.output.clone(),
+            })
+            .collect(),
+    }
+}
+
+fn copy_outputs(outputs: &I3Outputs) -> I3Outputs {
+    I3Outputs {
+        outputs: outputs
+            .outputs
+            .iter()
+            .map(|output| I3Output {
+                name: output.name.clone(),
+                active: output.active,
+                primary: output.primary,
+                current_workspace: output.current_workspace.clone(),
+                rect: output.rect,
+            })
+            .collect(),
+    }
+}
@@ -0,0 +1,46 @@
+use super::Backend;
+use crate::error::Error;
+use crate::tree::Con;
+use i3ipc::reply::{Outputs as I3Outputs, Workspaces as I3Workspaces};
+use serde_json::Value;
+
+/// Answers queries from another backend but only collects the commands, for
+/// `--dry-run`.
+pub struct DryRunBackend<'a> {
+    backend: &'a mut dyn Backend,
+    /// The commands that would have run, in order.
+    pub commands: Vec<String>,
+}
+
+impl<'a> DryRunBackend<'a> {
+    /// Wraps `backend`, which only gets queries from now on.
+    pub fn new(backend: &'a mut dyn Backend) -> Self {
+        DryRunBackend {
+            backend,
+            commands: Vec::new(),
+        }
+    }
+}
+
+impl Backend for DryRunBackend<'_> {
+    fn get_tree(&mut self) -> Result<Con, Error> {
+        self.backend.get_tree()
+    }
+
+    fn get_workspaces(&mut self) -> Result<I3Workspaces, Error> {
+        self.backend.get_workspaces()
+    }
+
+    fn get_outputs(&mut self) -> Result<I3Outputs, Error> {
+        self.backend.get_outputs()
+    }
+
+    fn get_snapshot(&mut self) -> Result<Value, Error> {
+        self.backend.get_snapshot()
+    }
+
+    fn run_command(&mut self, command: &str) -> Result<(), Error> {
+        self.commands.push(command.to_string());
+        Ok(())
+    }
+}
@@ -0,0 +1,201 @@
+use super::json::{boolean, parse_node, parse_outputs, parse_workspaces};
+use super::Backend;
+use crate::error::Error;
+use crate::tree::Con;
+use i3ipc::reply::{Outputs as I3Outputs, Workspaces as I3Workspaces};
+use serde_json::{json, Value};
+use std::path::Path;
+
+/// A layout loaded from JSON instead of a running i3, for `--tree-file` and
+/// the tests.
+///
+/// The JSON is either the raw output of `i3-msg -t get_tree`, or an object
+/// holding that tree under `tree` alongside the `workspaces` and `outputs`
+/// replies, as `i4 snapshot save` writes it. When the latter two are missing
+/// they are derived from the tree. Commands are recorded in `commands`
+/// rather than executed.
+pub struct FixtureBackend {
+    tree: Con,
+    /// The tree as it was loaded, for `get_snapshot`.
+    tree_json: Value,
+    workspaces: Value,
+    outputs: Value,
+    /// The commands run so far, in order.
+    pub commands: Vec<String>,
+}
+
+impl FixtureBackend {
+    /// Reads the layout from a JSON file.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json =
+            std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        FixtureBackend::from_json(&json)
+    }
+
+    /// Parses the layout from JSON, failing if any part of it is invalid.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let value: Value = serde_json::from_str(json).map_err(|err| err.to_string())?;
+        let (tree, workspaces, outputs) = match value.get("tree") {
+            Some(tree) => (
+                tree.clone(),
+                value.get("workspaces").cloned(),
+                value.get("outputs").cloned(),
+            ),
+            None => (value, None, None),
+        };
+
+        let workspaces = workspaces.unwrap_or_else(|| derive_workspaces(&tree));
+        let outputs = outputs.unwrap_or_else(|| derive_outputs(&tree));
+        // Parse everything up front so a broken fixture fails on load.
+        parse_workspaces(&workspaces)?;
+        parse_outputs(&outputs)?;
+
+        Ok(FixtureBackend {
+            tree: parse_node(&tree)?,
+            tree_json: tree,
+            workspaces,
+            outputs,
+            commands: Vec::new(),
+        })
+    }
+
+    /// Replaces the `workspaces` reply, e.g. to move the focus elsewhere.
+    #[cfg(test)]
+    pub fn set_workspaces(&mut self, json: &str) -> Result<(), String> {
+        let workspaces = serde_json::from_str(json).map_err(|err| err.to_string())?;
+        parse_workspaces(&workspaces)?;
+        self.workspaces = workspaces;
+        Ok(())
+    }
+}
+
+impl Backend for FixtureBackend {
+    fn get_tree(&mut self) -> Result<Con, Error> {
+        Ok(self.tree.clone())
+    }
+
+    fn get_workspaces(&mut self) -> Result<I3Workspaces, Error> {
+        Ok(parse_workspaces(&self.workspaces).expect("validated on load"))
+    }
+
+    fn get_outputs(&mut self) -> Result<I3Outputs, Error> {
+        Ok(parse_outputs(&self.outputs).expect("validated on load"))
+    }
+
+    fn get_snapshot(&mut self) -> Result<Value, Error> {
+        Ok(json!({
+            "tree": self.tree_json,
+            "workspaces": self.workspaces,
+            "outputs": self.outputs,
+        }))
+    }
+
+    fn run_command(&mut self, command: &str) -> Result<(), Error> {
+        info!("Not running `{}` against a tree file", command);
+        self.commands.push(command.to_string());
+        Ok(())
+    }
+}
+
+/// The real outputs of a tree, skipping i3's internal `__i3` output.
+fn output_nodes(tree: &Value) -> Vec<&Value> {
+    tree.get("nodes")
+        .and_then(Value::as_array)
+        .map(|outputs| {
+            outputs
+                .iter()
+                .filter(|output| output.get("name").and_then(Value::as_str) != Some("__i3"))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The workspaces of an output, in tree order, and the id of the one currently
+/// shown. i3 keeps them in the output's `content` container while sway puts
+/// them directly under the output.
+fn workspace_nodes(output: &Value) -> (Vec<&Value>, Option<i64>) {
+    let container = output
+        .get("nodes")
+        .and_then(Value::as_array)
+        .and_then(|nodes| {
+            nodes
+                .iter()
+                .find(|node| node.get("name").and_then(Value::as_str) == Some("content"))
+        })
+        .unwrap_or(output);
+    (
+        container
+            .get("nodes")
+            .and_then(Value::as_array)
+            .map(|nodes| {
+                nodes
+                    .iter()
+                    .filter(|node| node.get("type").and_then(Value::as_str) == Some("workspace"))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        container
+            .get("focus")
+            .and_then(Value::as_array)
+            .and_then(|focus| focus.first())
+            .and_then(Value::as_i64),
+    )
+}
+
+fn contains_focused(node: &Value) -> bool {
+    boolean(node, "focused")
+        || ["nodes", "floating_nodes"].iter().any(|key| {
+            node.get(key)
+                .and_then(Value::as_array)
+                .is_some_and(|children| children.iter().any(contains_focused))
+        })
+}
+
+fn derive_workspaces(tree: &Value) -> Value {
+    let mut workspaces = Vec::new();
+    for output in output_nodes(tree) {
+        let (nodes, current) = workspace_nodes(output);
+        for workspace in nodes {
+            let name = workspace
+                .get("name")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            let num = name
+                .split(|c: char| !c.is_ascii_digit())
+                .next()
+                .and_then(|digits| digits.parse::<i64>().ok())
+                .unwrap_or(-1);
+            workspaces.push(json!({
+                "num": num,
+                "name": name,
+                "visible": workspace.get("id").and_then(Value::as_i64) == current,
+                "focused": contains_focused(workspace),
+                "urgent": boolean(workspace, "urgent"),
+                "rect": workspace.get("rect"),
+                "output": output.get("name"),
+            }));
+        }
+    }
+    Value::Array(workspaces)
+}
+
+fn derive_outputs(tree: &Value) -> Value {
+    let outputs = output_nodes(tree)
+        .into_iter()
+        .map(|output| {
+            let (nodes, current) = workspace_nodes(output);
+            let current_workspace = nodes
+                .into_iter()
+                .find(|workspace| workspace.get("id").and_then(Value::as_i64) == current)
+                .and_then(|workspace| workspace.get("name"));
+            json!({
+                "name": output.get("name"),
+                "active": true,
+                "primary": false,
+                "current_workspace": current_workspace,
+                "rect": output.get("rect"),
+            })
+        })
+        .collect();
+    Value::Array(outputs)
+}
@@ -0,0 +1,27 @@
+use super::IpcBackend;
+use crate::error::Error;
+use i3ipc::EstablishError;
+use std::io;
+use std::process::Command;
+
+/// Connects to the i3 session whose socket is at `$I3SOCK`, or wherever
+/// `i3 --get-socketpath` says, the way i3ipc finds it.
+pub fn connect() -> Result<IpcBackend, Error> {
+    let path = match std::env::var("I3SOCK") {
+        Ok(path) => path,
+        Err(_) => {
+            socket_path().map_err(|err| Error::Connect(EstablishError::GetSocketPathError(err)))?
+        }
+    };
+    IpcBackend::connect(&path)
+}
+
+fn socket_path() -> io::Result<String> {
+    let output = Command::new("i3").arg("--get-socketpath").output()?;
+    if !output.status.success() {
+        return Err(io::Error::other("i3 --get-socketpath failed"));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .trim_end()
+        .to_string())
+}
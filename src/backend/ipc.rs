@@ -0,0 +1,131 @@
+use super::json::{parse_node, parse_outputs, parse_workspaces};
+use super::Backend;
+use crate::error::Error;
+use crate::tree::Con;
+use i3ipc::reply::{Outputs as I3Outputs, Workspaces as I3Workspaces};
+use i3ipc::{EstablishError, MessageError};
+use serde::de::Error as _;
+use serde_json::{json, Value};
+use std::io::{self, Read, Write};
+use std::os::unix::net::UnixStream;
+
+const MAGIC: &[u8; 6] = b"i3-ipc";
+const RUN_COMMAND: u32 = 0;
+const GET_WORKSPACES: u32 = 1;
+const GET_OUTPUTS: u32 = 3;
+const GET_TREE: u32 = 4;
+
+/// An i3 or sway session reached through its IPC socket.
+///
+/// i3ipc only knows some of the fields i3 sends, such as neither `marks` nor
+/// `floating`, and none of the ones sway adds for Wayland views, so the
+/// replies are parsed here.
+pub struct IpcBackend {
+    stream: UnixStream,
+}
+
+impl IpcBackend {
+    /// Connects to the socket at `path`.
+    pub fn connect(path: &str) -> Result<Self, Error> {
+        let stream = UnixStream::connect(path)
+            .map_err(|err| Error::Connect(EstablishError::SocketError(err)))?;
+        Ok(IpcBackend { stream })
+    }
+
+    /// Sends one message and reads its reply: both are framed as the `i3-ipc`
+    /// magic, the payload length and the message type, then the payload. The
+    /// reply has to be of the type asked for.
+    fn request(&mut self, message_type: u32, payload: &str) -> Result<Value, MessageError> {
+        let mut message = Vec::with_capacity(14 + payload.len());
+        message.extend_from_slice(MAGIC);
+        message.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        message.extend_from_slice(&message_type.to_le_bytes());
+        message.extend_from_slice(payload.as_bytes());
+        self.stream
+            .write_all(&message)
+            .map_err(MessageError::Send)?;
+
+        let mut header = [0u8; 14];
+        self.stream
+            .read_exact(&mut header)
+            .map_err(MessageError::Receive)?;
+        if &header[..6] != MAGIC {
+            return Err(MessageError::Receive(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Reply does not start with the i3-ipc magic",
+            )));
+        }
+        let length = u32::from_le_bytes([header[6], header[7], header[8], header[9]]) as usize;
+        let mut body = vec![0u8; length];
+        self.stream
+            .read_exact(&mut body)
+            .map_err(MessageError::Receive)?;
+        let reply_type = u32::from_le_bytes([header[10], header[11], header[12], header[13]]);
+        if reply_type != message_type {
+            return Err(MessageError::Receive(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Reply of type {} to a message of type {}",
+                    reply_type, message_type
+                ),
+            )));
+        }
+        serde_json::from_slice(&body).map_err(MessageError::JsonCouldntParse)
+    }
+}
+
+fn invalid_reply(err: String) -> Error {
+    Error::Tree(MessageError::JsonCouldntParse(serde_json::Error::custom(
+        err,
+    )))
+}
+
+impl Backend for IpcBackend {
+    fn get_tree(&mut self) -> Result<Con, Error> {
+        let reply = self.request(GET_TREE, "")?;
+        parse_node(&reply).map_err(invalid_reply)
+    }
+
+    fn get_workspaces(&mut self) -> Result<I3Workspaces, Error> {
+        let reply = self.request(GET_WORKSPACES, "")?;
+        parse_workspaces(&reply).map_err(invalid_reply)
+    }
+
+    fn get_outputs(&mut self) -> Result<I3Outputs, Error> {
+        let reply = self.request(GET_OUTPUTS, "")?;
+        parse_outputs(&reply).map_err(invalid_reply)
+    }
+
+    fn get_snapshot(&mut self) -> Result<Value, Error> {
+        Ok(json!({
+            "tree": self.request(GET_TREE, "")?,
+            "workspaces": self.request(GET_WORKSPACES, "")?,
+            "outputs": self.request(GET_OUTPUTS, "")?,
+        }))
+    }
+
+    fn run_command(&mut self, command: &str) -> Result<(), Error> {
+        let reply = self
+            .request(RUN_COMMAND, command)
+            .map_err(|err| Error::Command {
+                command: command.to_string(),
+                errors: vec![err.to_string()],
+            })?;
+        let outcomes = reply.as_array().cloned().unwrap_or_default();
+        if outcomes
+            .iter()
+            .all(|outcome| outcome.get("success").and_then(Value::as_bool) == Some(true))
+        {
+            Ok(())
+        } else {
+            Err(Error::Command {
+                command: command.to_string(),
+                errors: outcomes
+                    .iter()
+                    .filter_map(|outcome| outcome.get("error").and_then(Value::as_str))
+                    .map(String::from)
+                    .collect(),
+            })
+        }
+    }
+}
@@ -0,0 +1,264 @@
+//! Parsing of the JSON replies shared by i3 and sway.
+
+use crate::tree::{Con, WindowProperties};
+use i3ipc::reply::{
+    NodeLayout as I3NodeLayout, NodeType as I3NodeType, Output as I3Output, Outputs as I3Outputs,
+    Workspace as I3Workspace, Workspaces as I3Workspaces,
+};
+use serde_json::{json, Value};
+
+fn field<'a>(value: &'a Value, key: &str) -> Result<&'a Value, String> {
+    value
+        .get(key)
+        .ok_or_else(|| format!("Missing field `{}` in {}", key, value))
+}
+
+fn string(value: &Value, key: &str) -> Result<String, String> {
+    field(value, key)?
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| format!("Field `{}` is not a string", key))
+}
+
+fn optional_string(value: &Value, key: &str) -> Option<String> {
+    value.get(key).and_then(Value::as_str).map(String::from)
+}
+
+pub fn boolean(value: &Value, key: &str) -> bool {
+    value.get(key).and_then(Value::as_bool).unwrap_or(false)
+}
+
+fn rect(value: &Value, key: &str) -> Result<(i32, i32, i32, i32), String> {
+    let rect = match value.get(key) {
+        Some(rect) => rect,
+        None => return Ok((0, 0, 0, 0)),
+    };
+    let coordinate = |name: &str| -> Result<i32, String> {
+        field(rect, name)?
+            .as_i64()
+            .map(|n| n as i32)
+            .ok_or_else(|| format!("Field `{}.{}` is not an integer", key, name))
+    };
+    Ok((
+        coordinate("x")?,
+        coordinate("y")?,
+        coordinate("width")?,
+        coordinate("height")?,
+    ))
+}
+
+fn nodes(value: &Value, key: &str) -> Result<Vec<Con>, String> {
+    match value.get(key).and_then(Value::as_array) {
+        Some(nodes) => nodes.iter().map(parse_node).collect(),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Builds a `Con` from the JSON i3 or sway send for `get_tree`. Only `id` and
+/// `type` are required so that hand-written fixtures can stay short.
+pub fn parse_node(value: &Value) -> Result<Con, String> {
+    let nodetype = match string(value, "type")?.as_str() {
+        "root" => I3NodeType::Root,
+        "output" => I3NodeType::Output,
+        "con" => I3NodeType::Con,
+        "floating_con" => I3NodeType::FloatingCon,
+        "workspace" => I3NodeType::Workspace,
+        "dockarea" => I3NodeType::DockArea,
+        _ => I3NodeType::Unknown,
+    };
+    let layout = match value.get("layout").and_then(Value::as_str) {
+        Some("splith") => I3NodeLayout::SplitH,
+        Some("splitv") => I3NodeLayout::SplitV,
+        Some("stacked") => I3NodeLayout::Stacked,
+        Some("tabbed") => I3NodeLayout::Tabbed,
+        Some("dockarea") => I3NodeLayout::DockArea,
+        Some("output") => I3NodeLayout::Output,
+        _ => I3NodeLayout::Unknown,
+    };
+    // i3 says `auto_on` or `user_on`, sway uses the node type instead.
+    let floating = nodetype == I3NodeType::FloatingCon
+        || optional_string(value, "floating").is_some_and(|floating| floating.ends_with("_on"));
+    Ok(Con {
+        id: field(value, "id")?
+            .as_i64()
+            .ok_or("Field `id` is not an integer")?,
+        name: optional_string(value, "name"),
+        nodetype,
+        layout,
+        rect: rect(value, "rect")?,
+        focused: boolean(value, "focused"),
+        focus: value
+            .get("focus")
+            .and_then(Value::as_array)
+            .map(|ids| ids.iter().filter_map(Value::as_i64).collect())
+            .unwrap_or_default(),
+        nodes: nodes(value, "nodes")?,
+        floating_nodes: nodes(value, "floating_nodes")?,
+        window: value
+            .get("window")
+            .and_then(Value::as_i64)
+            .map(|w| w as i32),
+        window_properties: value
+            .get("window_properties")
+            .map(|properties| WindowProperties {
+                class: optional_string(properties, "class"),
+                instance: optional_string(properties, "instance"),
+                title: optional_string(properties, "title"),
+            }),
+        urgent: boolean(value, "urgent"),
+        floating,
+        marks: value
+            .get("marks")
+            .and_then(Value::as_array)
+            .map(|marks| {
+                marks
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default(),
+        app_id: optional_string(value, "app_id"),
+        pid: value
+            .get("pid")
+            .and_then(Value::as_i64)
+            .map(|pid| pid as i32),
+        shell: optional_string(value, "shell"),
+    })
+}
+
+pub fn parse_workspaces(value: &Value) -> Result<I3Workspaces, String> {
+    let workspaces = value
+        .as_array()
+        .ok_or("`workspaces` is not an array")?
+        .iter()
+        .map(|workspace| {
+            Ok(I3Workspace {
+                num: workspace.get("num").and_then(Value::as_i64).unwrap_or(-1) as i32,
+                name: string(workspace, "name")?,
+                visible: boolean(workspace, "visible"),
+                focused: boolean(workspace, "focused"),
+                urgent: boolean(workspace, "urgent"),
+                rect: rect(workspace, "rect")?,
+                output: string(workspace, "output")?,
+            })
+        })
+        .collect::<Result<_, String>>()?;
+    Ok(I3Workspaces { workspaces })
+}
+
+pub fn parse_outputs(value: &Value) -> Result<I3Outputs, String> {
+    let outputs = value
+        .as_array()
+        .ok_or("`outputs` is not an array")?
+        .iter()
+        .map(|output| {
+            Ok(I3Output {
+                name: string(output, "name")?,
+                active: boolean(output, "active"),
+                primary: boolean(output, "primary"),
+                current_workspace: output
+                    .get("current_workspace")
+                    .and_then(Value::as_str)
+                    .map(String::from),
+                rect: rect(output, "rect")?,
+            })
+        })
+        .collect::<Result<_, String>>()?;
+    Ok(I3Outputs { outputs })
+}
+
+fn rect_value(rect: (i32, i32, i32, i32)) -> Value {
+    let (x, y, width, height) = rect;
+    json!({ "x": x, "y": y, "width": width, "height": height })
+}
+
+/// The inverse of `parse_node`: the JSON i3 would send for `con`, with the
+/// fields i4 reads.
+pub fn node_value(con: &Con) -> Value {
+    let nodetype = match con.nodetype {
+        I3NodeType::Root => "root",
+        I3NodeType::Output => "output",
+        I3NodeType::Con => "con",
+        I3NodeType::FloatingCon => "floating_con",
+        I3NodeType::Workspace => "workspace",
+        I3NodeType::DockArea => "dockarea",
+        I3NodeType::Unknown => "unknown",
+    };
+    let layout = match con.layout {
+        I3NodeLayout::SplitH => "splith",
+        I3NodeLayout::SplitV => "splitv",
+        I3NodeLayout::Stacked => "stacked",
+        I3NodeLayout::Tabbed => "tabbed",
+        I3NodeLayout::DockArea => "dockarea",
+        I3NodeLayout::Output => "output",
+        I3NodeLayout::Unknown => "unknown",
+    };
+    let mut value = json!({
+        "id": con.id,
+        "type": nodetype,
+        "name": con.name,
+        "layout": layout,
+        "rect": rect_value(con.rect),
+        "focused": con.focused,
+        "focus": con.focus,
+        "urgent": con.urgent,
+        "floating": if con.floating { "user_on" } else { "auto_off" },
+        "marks": con.marks,
+        "window": con.window,
+        "nodes": con.nodes.iter().map(node_value).collect::<Vec<_>>(),
+        "floating_nodes": con.floating_nodes.iter().map(node_value).collect::<Vec<_>>(),
+    });
+    if let Some(properties) = &con.window_properties {
+        value["window_properties"] = json!({
+            "class": properties.class,
+            "instance": properties.instance,
+            "title": properties.title,
+        });
+    }
+    // sway's view fields, only where sway sends them.
+    if let Some(app_id) = &con.app_id {
+        value["app_id"] = json!(app_id);
+    }
+    if let Some(pid) = con.pid {
+        value["pid"] = json!(pid);
+    }
+    if let Some(shell) = &con.shell {
+        value["shell"] = json!(shell);
+    }
+    value
+}
+
+pub fn workspaces_value(workspaces: &I3Workspaces) -> Value {
+    workspaces
+        .workspaces
+        .iter()
+        .map(|workspace| {
+            json!({
+                "num": workspace.num,
+                "name": workspace.name,
+                "visible": workspace.visible,
+                "focused": workspace.focused,
+                "urgent": workspace.urgent,
+                "rect": rect_value(workspace.rect),
+                "output": workspace.output,
+            })
+        })
+        .collect()
+}
+
+pub fn outputs_value(outputs: &I3Outputs) -> Value {
+    outputs
+        .outputs
+        .iter()
+        .map(|output| {
+            json!({
+                "name": output.name,
+                "active": output.active,
+                "primary": output.primary,
+                "current_workspace": output.current_workspace,
+                "rect": rect_value(output.rect),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,63 @@
+//! The window manager side of i4: everything that talks to i3 goes through a
+//! `Backend`, so the navigation logic can run against a live session or a
+//! tree loaded from disk.
+
+mod cache;
+mod dry_run;
+mod fixture;
+mod i3;
+mod ipc;
+mod json;
+mod sway;
+
+pub use cache::CachedBackend;
+pub use dry_run::DryRunBackend;
+pub use fixture::FixtureBackend;
+pub use ipc::IpcBackend;
+
+use crate::error::Error;
+use crate::tree::Con;
+use i3ipc::reply::{Outputs as I3Outputs, Workspaces as I3Workspaces};
+use serde_json::{json, Value};
+
+/// `Send` so that the daemon can share one between its threads.
+pub trait Backend: Send {
+    /// The layout tree, from the root down.
+    fn get_tree(&mut self) -> Result<Con, Error>;
+
+    /// Every workspace, with its output and whether it is visible.
+    fn get_workspaces(&mut self) -> Result<I3Workspaces, Error>;
+
+    /// Every output, with the workspace it is showing.
+    fn get_outputs(&mut self) -> Result<I3Outputs, Error>;
+
+    /// Runs an i3 command, failing if any of its outcomes was unsuccessful.
+    fn run_command(&mut self, command: &str) -> Result<(), Error>;
+
+    /// Everything the window manager reports, in the format `FixtureBackend`
+    /// loads, with every field it sent. Backends that only keep the parsed
+    /// replies rebuild them, as `snapshot` does.
+    fn get_snapshot(&mut self) -> Result<Value, Error> {
+        snapshot(self)
+    }
+}
+
+/// Connects to the running window manager: sway when `$SWAYSOCK` is set,
+/// i3 otherwise.
+pub fn connect() -> Result<Box<dyn Backend>, Error> {
+    if std::env::var_os("SWAYSOCK").is_some() {
+        Ok(Box::new(sway::connect()?))
+    } else {
+        Ok(Box::new(i3::connect()?))
+    }
+}
+
+/// Everything `backend` reports, in the format `FixtureBackend` loads, as i4
+/// parses it: the fields navigation doesn't use are left out.
+pub fn snapshot<B: Backend + ?Sized>(backend: &mut B) -> Result<Value, Error> {
+    Ok(json!({
+        "tree": json::node_value(&backend.get_tree()?),
+        "workspaces": json::workspaces_value(&backend.get_workspaces()?),
+        "outputs": json::outputs_value(&backend.get_outputs()?),
+    }))
+}
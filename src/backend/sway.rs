@@ -0,0 +1,15 @@
+use super::IpcBackend;
+use crate::error::Error;
+use i3ipc::EstablishError;
+use std::io;
+
+/// Connects to the sway session whose socket is at `$SWAYSOCK`.
+pub fn connect() -> Result<IpcBackend, Error> {
+    let path = std::env::var("SWAYSOCK").map_err(|_| {
+        Error::Connect(EstablishError::GetSocketPathError(io::Error::new(
+            io::ErrorKind::NotFound,
+            "SWAYSOCK is not set",
+        )))
+    })?;
+    IpcBackend::connect(&path)
+}
@@ -0,0 +1,305 @@
+use crate::config::Config;
+use crate::direction::{Direction, Scope, Wrap};
+use crate::error::EXIT_CODES_HELP;
+use crate::output::OutputFormat;
+use crate::query::Query;
+use clap::error::ErrorKind;
+use clap::{ArgAction, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "i4",
+    version,
+    about = "A grid like navigator for i3wm",
+    after_help = EXIT_CODES_HELP
+)]
+pub struct Cli {
+    /// Write debug information to the log file
+    #[arg(short, long, global = true)]
+    pub debug: bool,
+
+    /// Print the i3 commands instead of running them
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /// Run against a snapshot from `i4 snapshot save` instead of i3, printing
+    /// the i3 commands as --dry-run does
+    #[arg(long, global = true, value_name = "FILE")]
+    pub tree_file: Option<PathBuf>,
+
+    /// Log to this file instead of the default one [default: $XDG_STATE_HOME/i4/i4.log]
+    #[arg(long, global = true, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+
+    /// Log more: -v for info, -vv for debug, -vvv for trace
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Log less: -q for errors only, -qq for nothing
+    #[arg(short, long, global = true, action = ArgAction::Count, conflicts_with = "verbose")]
+    pub quiet: u8,
+
+    /// Output format for list and get
+    #[arg(short, long, value_enum, global = true, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+impl Cli {
+    /// The command line definition, with the defaults the config sets.
+    pub fn command_with(config: &Config) -> clap::Command {
+        let scope = name(config.scope);
+        let wrap = name(config.wrap);
+        let timeout = config.mru_timeout.to_string();
+        let navigation = |command: clap::Command| {
+            command
+                .mut_arg("scope", |arg| arg.default_value(scope.clone()))
+                .mut_arg("wrap", |arg| arg.default_value(wrap.clone()))
+                .mut_arg("timeout", |arg| arg.default_value(timeout.clone()))
+        };
+        Cli::command()
+            .mut_arg("format", |arg| arg.default_value(name(config.format)))
+            .mut_subcommand("focus", navigation)
+            .mut_subcommand("move", navigation)
+            .mut_subcommand("get", |command| {
+                command.mut_arg("wrap", |arg| arg.default_value(wrap.clone()))
+            })
+    }
+
+    pub fn parse_from_with<I, T>(args: I, config: &Config) -> Cli
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        Cli::try_parse_from_with(args, config).unwrap_or_else(|err| err.exit())
+    }
+
+    pub fn try_parse_from_with<I, T>(args: I, config: &Config) -> Result<Cli, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        let mut command = Cli::command_with(config);
+        let matches = command.try_get_matches_from_mut(args)?;
+        Cli::from_arg_matches(&matches)?.check(&mut command)
+    }
+
+    /// Catches the combinations clap can't express.
+    fn check(self, command: &mut clap::Command) -> Result<Cli, clap::Error> {
+        if let Command::List {
+            target: target @ (ListTarget::All | ListTarget::Focused),
+            filter: Some(_),
+        } = &self.command
+        {
+            return Err(command.error(
+                ErrorKind::ArgumentConflict,
+                format!("--filter can't be used with `list {}`", name(*target)),
+            ));
+        }
+        if let Command::Focus { navigation } | Command::Move { navigation } = &self.command {
+            if navigation.target != Target::Mru && navigation.n.is_some() {
+                return Err(command.error(
+                    ErrorKind::ArgumentConflict,
+                    format!("N can only be given to mru, not {}", navigation.target),
+                ));
+            }
+        }
+        Ok(self)
+    }
+}
+
+/// The name a value has on the command line.
+fn name<T: ValueEnum>(value: T) -> String {
+    value.to_possible_value().unwrap().get_name().to_string()
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// List nodes
+    List {
+        #[arg(value_enum)]
+        target: ListTarget,
+        /// Only list the windows matching an expression, e.g.
+        /// 'class=Firefox && workspace=2' (windows and visible only)
+        #[arg(long, value_name = "EXPRESSION", value_parser = Query::parse)]
+        filter: Option<Query>,
+    },
+    /// Print the neighbouring window
+    Get {
+        #[arg(value_enum)]
+        direction: Direction,
+        /// Where to continue when there is no window in that direction
+        #[arg(long, value_enum, default_value_t = Wrap::None)]
+        wrap: Wrap,
+    },
+    /// Focus a window
+    Focus {
+        #[command(flatten)]
+        navigation: Navigation,
+    },
+    /// Move a window
+    Move {
+        #[command(flatten)]
+        navigation: Navigation,
+    },
+    /// Switch between workspaces laid out as a grid on each output
+    Workspace {
+        /// Rows in each output's grid [default: from the config, or 3]
+        #[arg(long, value_parser = clap::value_parser!(i32).range(1..))]
+        rows: Option<i32>,
+        /// Columns in each output's grid [default: from the config, or 3]
+        #[arg(long, value_parser = clap::value_parser!(i32).range(1..))]
+        columns: Option<i32>,
+        #[command(subcommand)]
+        action: WorkspaceAction,
+    },
+    /// Serve commands from memory, following i3 events to stay up to date
+    Daemon,
+    /// Inspect the config file
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Save the layout to a file, for `--tree-file` or a bug report
+    Snapshot {
+        #[command(subcommand)]
+        action: SnapshotAction,
+    },
+    /// Print a shell completion script
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+/// How `focus` and `move` pick their target window.
+#[derive(Args, Clone, Copy, Debug)]
+pub struct Navigation {
+    #[arg(value_enum)]
+    pub target: Target,
+    /// For mru: how many windows back to go [default: 1]
+    #[arg(value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub n: Option<usize>,
+    /// How far previous and next may go from the focused window
+    #[arg(long, value_enum, default_value_t = Scope::Workspace)]
+    pub scope: Scope,
+    /// Where to continue when there is no window in that direction
+    #[arg(long, value_enum, default_value_t = Wrap::None)]
+    pub wrap: Wrap,
+    /// For mru-cycle: milliseconds without another step before the new
+    /// order is committed
+    #[arg(long, default_value_t = 1000)]
+    pub timeout: u64,
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum ConfigAction {
+    /// Validate the config and print the effective settings
+    Check {
+        /// The file to check instead of the usual config
+        file: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SnapshotAction {
+    /// Write the tree, workspaces and outputs to a JSON file
+    Save {
+        /// Where to write the snapshot, `-` for stdout
+        file: PathBuf,
+        /// Replace titles, classes and workspace names with pseudonyms
+        #[arg(long)]
+        anonymize: bool,
+    },
+}
+
+/// What `workspace` does with the adjacent cell of the grid, which is
+/// created if it doesn't exist yet.
+#[derive(Subcommand, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WorkspaceAction {
+    /// Switch to the workspace on the left
+    Left,
+    /// Switch to the workspace on the right
+    Right,
+    /// Switch to the workspace above
+    Up,
+    /// Switch to the workspace below
+    Down,
+    /// Move the focused container to the adjacent workspace and follow it
+    Move {
+        #[arg(value_enum)]
+        direction: Direction,
+    },
+}
+
+impl WorkspaceAction {
+    pub fn direction(&self) -> Direction {
+        match self {
+            WorkspaceAction::Left => Direction::Left,
+            WorkspaceAction::Right => Direction::Right,
+            WorkspaceAction::Up => Direction::Up,
+            WorkspaceAction::Down => Direction::Down,
+            WorkspaceAction::Move { direction } => *direction,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ListTarget {
+    All,
+    Focused,
+    Visible,
+    Windows,
+}
+
+/// The window a `focus` or `move` acts on, relative to the focused one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Target {
+    /// The window to the left
+    Left,
+    /// The window to the right
+    Right,
+    /// The window above
+    Up,
+    /// The window below
+    Down,
+    /// The window before this one in the tree
+    Previous,
+    /// The window after this one in the tree
+    Next,
+    /// The window focused before the current one
+    Back,
+    /// The nth most recently focused window
+    Mru,
+    /// Walk the most recently focused windows, Alt-Tab style
+    MruCycle,
+}
+
+impl Target {
+    pub fn direction(&self) -> Option<Direction> {
+        match self {
+            Target::Left => Some(Direction::Left),
+            Target::Right => Some(Direction::Right),
+            Target::Up => Some(Direction::Up),
+            Target::Down => Some(Direction::Down),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Target::Previous => write!(f, "previous"),
+            Target::Next => write!(f, "next"),
+            Target::Back => write!(f, "back"),
+            Target::Mru => write!(f, "mru"),
+            Target::MruCycle => write!(f, "mru-cycle"),
+            _ => write!(f, "{}", self.direction().unwrap()),
+        }
+    }
+}
@@ -0,0 +1,317 @@
+//! The config file, `$XDG_CONFIG_HOME/i4/config.toml`.
+//!
+//! Every setting is optional. The scalar ones become the defaults of the
+//! matching command line options, so a flag still wins over the file.
+//!
+//! ```toml
+//! scope = "workspace"
+//! wrap = "none"
+//! format = "text"
+//! mru_timeout = 1000
+//!
+//! [grid]
+//! rows = 3
+//! columns = 3
+//!
+//! [grid.outputs.HDMI-1]
+//! rows = 2
+//! columns = 2
+//!
+//! [colours]
+//! window = "bold,cyan"
+//!
+//! [log]
+//! debug = false
+//! file = "/home/me/.local/state/i4/i4.log"
+//! max_size = 1048576
+//! keep = 3
+//! ```
+
+use crate::direction::{Scope, Wrap};
+use crate::error::Error;
+use crate::grid::Grid;
+use crate::logger::Rotation;
+use crate::output::OutputFormat;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// The settings from the config file, or their defaults.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Default `--scope` of `focus` and `move`.
+    pub scope: Scope,
+    /// Default `--wrap` of `focus`, `move` and `get`.
+    pub wrap: Wrap,
+    /// Default `--format`.
+    pub format: OutputFormat,
+    /// Default `--timeout` of `focus mru-cycle`, in milliseconds.
+    pub mru_timeout: u64,
+    /// The size of the workspace grid, `[grid]`.
+    pub grid: GridConfig,
+    /// How `list` colours each node type, `[colours]`.
+    pub colours: Colours,
+    /// Where and how much to log, `[log]`.
+    pub log: LogConfig,
+}
+
+/// The workspace grid: `rows` × `columns` on every output, unless the
+/// output has its own size under `outputs`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GridConfig {
+    /// Default rows of every output's grid.
+    pub rows: i32,
+    /// Default columns of every output's grid.
+    pub columns: i32,
+    /// Grids of their own, by output name.
+    pub outputs: BTreeMap<String, Grid>,
+}
+
+/// `style!` specs for each `NodeType`, as used by `list` in text format.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Colours {
+    /// For `NodeType::Root`.
+    pub root: String,
+    /// For `NodeType::Output`.
+    pub output: String,
+    /// For `NodeType::DockArea`.
+    pub dockarea: String,
+    /// For `NodeType::Workspace`.
+    pub workspace: String,
+    /// For `NodeType::Split`.
+    pub split: String,
+    /// For `NodeType::FloatingContainer`.
+    pub floating_container: String,
+    /// For `NodeType::Window`.
+    pub window: String,
+    /// For `NodeType::Unknown`.
+    pub unknown: String,
+}
+
+/// Logging: whether `--debug` is always on, and the log file's location
+/// and rotation.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// Log to `file` even without `--debug`.
+    pub debug: bool,
+    /// Defaults to `i4.log` in the state directory.
+    pub file: Option<PathBuf>,
+    /// Size in bytes past which the log file is rotated.
+    pub max_size: u64,
+    /// How many rotated log files to keep.
+    pub keep: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            scope: Scope::Workspace,
+            wrap: Wrap::None,
+            format: OutputFormat::Text,
+            mru_timeout: 1000,
+            grid: GridConfig::default(),
+            colours: Colours::default(),
+            log: LogConfig::default(),
+        }
+    }
+}
+
+impl Default for GridConfig {
+    fn default() -> Self {
+        GridConfig {
+            rows: 3,
+            columns: 3,
+            outputs: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for Colours {
+    fn default() -> Self {
+        Colours {
+            root: "bold,blue".to_string(),
+            output: "bold,green".to_string(),
+            dockarea: "bold,black".to_string(),
+            workspace: "bold,yellow".to_string(),
+            split: "bold,magenta".to_string(),
+            floating_container: "bold,red".to_string(),
+            window: "bold,cyan".to_string(),
+            unknown: "bold,white".to_string(),
+        }
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            debug: false,
+            file: None,
+            max_size: 1024 * 1024,
+            keep: 3,
+        }
+    }
+}
+
+/// The words `style!` understands.
+const STYLES: &[&str] = &[
+    "black",
+    "red",
+    "green",
+    "yellow",
+    "blue",
+    "magenta",
+    "cyan",
+    "white",
+    "default",
+    "bold",
+    "dim",
+    "italic",
+    "underline",
+    "blink",
+    "reverse",
+    "hide",
+];
+
+impl GridConfig {
+    /// The grid of the output called `name`.
+    pub fn for_output(&self, name: &str) -> Grid {
+        self.outputs.get(name).copied().unwrap_or(Grid {
+            rows: self.rows,
+            columns: self.columns,
+        })
+    }
+}
+
+impl LogConfig {
+    /// When the log file gets rotated.
+    pub fn rotation(&self) -> Rotation {
+        Rotation {
+            max_size: self.max_size,
+            keep: self.keep,
+        }
+    }
+}
+
+impl Config {
+    /// Parses and validates the contents of a config file.
+    pub fn parse(toml: &str) -> Result<Config, String> {
+        let config: Config = toml::from_str(toml).map_err(|err| err.to_string())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Catches what the types alone don't.
+    fn validate(&self) -> Result<(), String> {
+        let grids = std::iter::once(("grid".to_string(), self.grid.for_output(""))).chain(
+            self.grid
+                .outputs
+                .iter()
+                .map(|(name, grid)| (format!("grid.outputs.{}", name), *grid)),
+        );
+        for (key, grid) in grids {
+            if grid.rows < 1 || grid.columns < 1 {
+                return Err(format!("`{}` needs at least one row and column", key));
+            }
+        }
+
+        if self.log.max_size == 0 {
+            return Err("`log.max_size` must be more than 0".to_string());
+        }
+
+        let colours = [
+            ("root", &self.colours.root),
+            ("output", &self.colours.output),
+            ("dockarea", &self.colours.dockarea),
+            ("workspace", &self.colours.workspace),
+            ("split", &self.colours.split),
+            ("floating_container", &self.colours.floating_container),
+            ("window", &self.colours.window),
+            ("unknown", &self.colours.unknown),
+        ];
+        for (key, style) in colours {
+            for word in style.split(',').map(str::trim) {
+                if !STYLES.contains(&word) {
+                    return Err(format!(
+                        "Unknown style `{}` in `colours.{}`, expected one of: {}",
+                        word,
+                        key,
+                        STYLES.join(", ")
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `$I4_CONFIG` if set, otherwise `i4/config.toml` in `$XDG_CONFIG_HOME`,
+/// falling back to `~/.config`.
+pub fn path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("I4_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    let dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => Path::new(&std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(dir.join("i4/config.toml"))
+}
+
+/// Reads the config at `path`. A missing file is not an error: every
+/// setting keeps its default.
+pub fn load_from(path: &Path) -> Result<Config, Error> {
+    match std::fs::read_to_string(path) {
+        Ok(toml) => Config::parse(&toml)
+            .map_err(|err| Error::Config(format!("{}: {}", path.display(), err))),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(err) => Err(Error::Config(format!("{}: {}", path.display(), err))),
+    }
+}
+
+/// Reads the config at the usual `path()`.
+pub fn load() -> Result<Config, Error> {
+    match path() {
+        Some(path) => load_from(&path),
+        None => Ok(Config::default()),
+    }
+}
+
+static COLOURS: OnceLock<Colours> = OnceLock::new();
+
+/// Makes `colours` the scheme `NodeType`'s `Display` uses. Only the first
+/// call has an effect.
+pub fn set_colours(colours: Colours) {
+    let _ = COLOURS.set(colours);
+}
+
+/// The colour scheme set with `set_colours`, or the default one.
+pub fn colours() -> &'static Colours {
+    COLOURS.get_or_init(Colours::default)
+}
+
+/// `i4 config check`: validates the config and prints the effective values.
+pub fn check(file: Option<PathBuf>, format: OutputFormat) -> Result<String, Error> {
+    let path = file
+        .or_else(path)
+        .ok_or_else(|| Error::Config("neither XDG_CONFIG_HOME nor HOME is set".to_string()))?;
+    let config = load_from(&path)?;
+    let status = if path.exists() {
+        "is valid"
+    } else {
+        "does not exist"
+    };
+    Ok(match format {
+        OutputFormat::Json => serde_json::to_string_pretty(&config).unwrap(),
+        _ => format!(
+            "# {} {}; effective settings:\n{}",
+            path.display(),
+            status,
+            toml::to_string(&config).unwrap().trim_end()
+        ),
+    })
+}
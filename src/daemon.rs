@@ -0,0 +1,237 @@
+//! `i4 daemon`: keeps the layout in memory and runs commands for clients.
+//!
+//! The daemon listens on a Unix socket. Each client sends its command line as
+//! one JSON line and gets back one JSON line with what the command printed and
+//! how it ended. Any i4 invocation first tries the socket and only talks to i3
+//! itself when no daemon is listening.
+
+use crate::backend::{self, CachedBackend};
+use crate::cli::{Cli, Command};
+use crate::config::Config;
+use crate::error::Error;
+use crate::execute_cli;
+use crate::history::{self, History};
+use crate::macros::{self, Stream};
+use i3ipc::event::{inner::WindowChange, Event};
+use i3ipc::{I3EventListener, MessageError, Subscription};
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// What the daemon keeps in memory between requests.
+pub struct State {
+    pub backend: CachedBackend,
+    pub history: History,
+    pub config: Config,
+}
+
+/// How long a client gets to send its request and read the reply. Clients
+/// are answered one at a time, so one that connects and then goes quiet
+/// holds up every keypress after it until this runs out.
+const CLIENT_TIMEOUT: Duration = Duration::from_millis(500);
+
+#[derive(Serialize, Deserialize)]
+struct Request {
+    /// The command line as bytes, since it needn't be UTF-8.
+    args: Vec<Vec<u8>>,
+    /// Whether the client's stdout takes colour codes.
+    #[serde(default)]
+    colour: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Reply {
+    output: String,
+    error: Option<String>,
+    code: i32,
+}
+
+/// `$I4_SOCKET` if set, otherwise `i4.sock` in `$XDG_RUNTIME_DIR`, or in the
+/// temporary directory when there is no runtime directory.
+pub fn socket_path() -> PathBuf {
+    if let Some(path) = std::env::var_os("I4_SOCKET") {
+        return PathBuf::from(path);
+    }
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => Path::new(&dir).join("i4.sock"),
+        None => std::env::temp_dir().join(format!(
+            "i4-{}.sock",
+            std::env::var("USER").unwrap_or_default()
+        )),
+    }
+}
+
+/// Runs the daemon until i3 goes away.
+pub fn run(path: &Path, config: Config) -> Result<(), Error> {
+    let state = Arc::new(Mutex::new(State {
+        backend: CachedBackend::new(backend::connect()?),
+        history: history::load(),
+        config,
+    }));
+    state.lock().unwrap().backend.refresh()?;
+
+    let mut events = I3EventListener::connect()?;
+    events.subscribe(&[
+        Subscription::Window,
+        Subscription::Workspace,
+        Subscription::Output,
+    ])?;
+    let listener = bind(path)?;
+    info!("Listening on {}", path.display());
+
+    let events_state = Arc::clone(&state);
+    let socket = path.to_path_buf();
+    thread::spawn(move || {
+        for event in events.listen() {
+            if let Err(MessageError::Receive(err)) = event {
+                // i3 exited or restarted: the cache can't be kept up to
+                // date any more, so make clients fall back to i3 directly.
+                let _ = std::fs::remove_file(&socket);
+                let err = Error::Tree(MessageError::Receive(err));
+                eprintln!("Error: {}", err);
+                std::process::exit(err.exit_code());
+            }
+            let mut state = events_state.lock().unwrap();
+            if let Ok(Event::WindowEvent(info)) = &event {
+                if let WindowChange::Focus = info.change {
+                    state.history.record(info.container.id, history::now());
+                    if let Err(err) = state.history.save() {
+                        warning!("Could not save the focus history: {}", err);
+                    }
+                }
+            }
+            // Refetch right away rather than on the next request, so that
+            // the next keypress is answered from memory. Events i3ipc can't
+            // parse still mean the layout changed.
+            let _ = state.backend.refresh();
+        }
+    });
+
+    serve(listener, &state);
+    Ok(())
+}
+
+/// Binds the socket, replacing one left behind by a daemon that is gone.
+fn bind(path: &Path) -> Result<UnixListener, Error> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(Error::Daemon(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("already running on {}", path.display()),
+            )));
+        }
+        std::fs::remove_file(path).map_err(Error::Daemon)?;
+    }
+    UnixListener::bind(path).map_err(Error::Daemon)
+}
+
+/// Answers clients one at a time.
+pub fn serve(listener: UnixListener, state: &Mutex<State>) {
+    for stream in listener.incoming() {
+        let result = stream.and_then(|stream| handle(stream, state));
+        if let Err(err) = result {
+            warning!("Client failed: {}", err);
+        }
+    }
+}
+
+fn handle(stream: UnixStream, state: &Mutex<State>) -> io::Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let request: Request = serde_json::from_str(&line)?;
+    let reply = answer(request, state);
+    serde_json::to_writer(&stream, &reply)?;
+    writeln!(&stream)
+}
+
+fn answer(request: Request, state: &Mutex<State>) -> Reply {
+    let mut state = state.lock().unwrap();
+    // Requests are answered one at a time, so this holds until the next.
+    macros::set_colour(request.colour);
+    let State {
+        backend,
+        history,
+        config,
+    } = &mut *state;
+    let args = std::iter::once(OsString::from("i4"))
+        .chain(request.args.into_iter().map(OsString::from_vec));
+    let cli = match Cli::try_parse_from_with(args, config) {
+        Ok(cli) => cli,
+        Err(err) => {
+            return Reply {
+                output: String::new(),
+                error: Some(err.to_string().trim_end().to_string()),
+                code: err.exit_code(),
+            }
+        }
+    };
+    if let Command::Daemon
+    | Command::Config { .. }
+    | Command::Snapshot { .. }
+    | Command::Completions { .. } = cli.command
+    {
+        return Reply {
+            output: String::new(),
+            error: Some("Not supported through the daemon".to_string()),
+            code: 2,
+        };
+    }
+
+    let result = execute_cli(cli, backend, history, config);
+    if let Err(err) = history.save() {
+        warning!("Could not save the focus history: {}", err);
+    }
+    match result {
+        Ok(output) => Reply {
+            output,
+            error: None,
+            code: 0,
+        },
+        Err(err) => Reply {
+            output: String::new(),
+            error: Some(err.to_string()),
+            code: err.exit_code(),
+        },
+    }
+}
+
+/// Runs a command line through the daemon listening on `path`. Returns
+/// `Ok(None)` if there is none, in which case the caller should run the
+/// command itself.
+pub fn forward(path: &Path, args: &[OsString]) -> Result<Option<String>, Error> {
+    let stream = match UnixStream::connect(path) {
+        Ok(stream) => stream,
+        Err(_) => return Ok(None),
+    };
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .map_err(Error::Daemon)?;
+
+    let request = Request {
+        args: args.iter().map(|arg| arg.as_bytes().to_vec()).collect(),
+        colour: macros::colour(Stream::Stdout),
+    };
+    serde_json::to_writer(&stream, &request).map_err(|err| Error::Daemon(err.into()))?;
+    writeln!(&stream).map_err(Error::Daemon)?;
+
+    let mut line = String::new();
+    BufReader::new(&stream)
+        .read_line(&mut line)
+        .map_err(Error::Daemon)?;
+    let reply: Reply = serde_json::from_str(&line).map_err(|err| Error::Daemon(err.into()))?;
+    match reply.error {
+        Some(message) => Err(Error::Remote {
+            message,
+            code: reply.code,
+        }),
+        None => Ok(Some(reply.output)),
+    }
+}
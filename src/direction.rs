@@ -0,0 +1,151 @@
+//! Directions and rects: which windows lie beyond which, and how far.
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// An i3 rect: (x, y, width, height) in absolute display coordinates.
+pub type Rect = (i32, i32, i32, i32);
+
+/// A direction on screen, to look for a neighbouring window or workspace in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Direction {
+    /// To the left
+    Left,
+    /// To the right
+    Right,
+    /// Above
+    Up,
+    /// Below
+    Down,
+}
+
+impl std::fmt::Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Direction::Left => write!(f, "left"),
+            Direction::Right => write!(f, "right"),
+            Direction::Up => write!(f, "up"),
+            Direction::Down => write!(f, "down"),
+        }
+    }
+}
+
+/// How far navigation may go around when it runs out of windows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Wrap {
+    /// Stop at the last window
+    None,
+    /// Cycle within the current workspace
+    Workspace,
+    /// Cycle within the current output
+    Output,
+    /// Cycle across all outputs
+    Global,
+}
+
+/// How far `previous` and `next` may walk from the focused window. They never
+/// enter a workspace its output isn't showing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// The container holding the focused window
+    Container,
+    /// The current workspace
+    Workspace,
+    /// What the current output shows
+    Output,
+    /// What every output shows
+    All,
+}
+
+impl Direction {
+    /// Distance from the edge of `from` to the facing edge of `to`, or `None`
+    /// if `to` does not lie entirely beyond `from` in this direction.
+    pub fn distance(&self, from: Rect, to: Rect) -> Option<i32> {
+        let (fx, fy, fw, fh) = from;
+        let (tx, ty, tw, th) = to;
+        let distance = match self {
+            Direction::Left => fx - (tx + tw),
+            Direction::Right => tx - (fx + fw),
+            Direction::Up => fy - (ty + th),
+            Direction::Down => ty - (fy + fh),
+        };
+        if distance >= 0 {
+            Some(distance)
+        } else {
+            None
+        }
+    }
+
+    /// Length of the overlap between `from` and `to` on the axis perpendicular
+    /// to this direction. Zero or less means the rects don't line up.
+    pub fn overlap(&self, from: Rect, to: Rect) -> i32 {
+        let (fx, fy, fw, fh) = from;
+        let (tx, ty, tw, th) = to;
+        match self {
+            Direction::Left | Direction::Right => (fy + fh).min(ty + th) - fy.max(ty),
+            Direction::Up | Direction::Down => (fx + fw).min(tx + tw) - fx.max(tx),
+        }
+    }
+
+    /// Picks the rect nearest to `from` in this direction among those that
+    /// overlap it. Ties are broken by the larger overlap, then by list order.
+    pub fn nearest(&self, from: Rect, candidates: &[Rect]) -> Option<usize> {
+        candidates
+            .iter()
+            .enumerate()
+            .filter_map(|(i, rect)| {
+                let distance = self.distance(from, *rect)?;
+                let overlap = self.overlap(from, *rect);
+                if overlap > 0 {
+                    Some((i, distance, overlap))
+                } else {
+                    None
+                }
+            })
+            .min_by(|a, b| a.1.cmp(&b.1).then(b.2.cmp(&a.2)).then(a.0.cmp(&b.0)))
+            .map(|(i, _, _)| i)
+    }
+
+    /// Like `nearest`, but falls back to rects that don't line up with `from`,
+    /// taking the one whose centre is closest on the perpendicular axis. Used
+    /// across outputs, which are often offset from each other.
+    pub fn closest(&self, from: Rect, candidates: &[Rect]) -> Option<usize> {
+        self.nearest(from, candidates).or_else(|| {
+            candidates
+                .iter()
+                .enumerate()
+                .filter_map(|(i, rect)| {
+                    let distance = self.distance(from, *rect)?;
+                    Some((i, self.offset(from, *rect), distance))
+                })
+                .min_by(|a, b| a.1.cmp(&b.1).then(a.2.cmp(&b.2)).then(a.0.cmp(&b.0)))
+                .map(|(i, _, _)| i)
+        })
+    }
+
+    /// Distance between the centres of `from` and `to` on the axis
+    /// perpendicular to this direction.
+    fn offset(&self, from: Rect, to: Rect) -> i32 {
+        let (fx, fy, fw, fh) = from;
+        let (tx, ty, tw, th) = to;
+        match self {
+            Direction::Left | Direction::Right => ((fy * 2 + fh) - (ty * 2 + th)).abs() / 2,
+            Direction::Up | Direction::Down => ((fx * 2 + fw) - (tx * 2 + tw)).abs() / 2,
+        }
+    }
+
+    /// Moves `from` just past the edge of `region` opposite to this direction,
+    /// so that searching from there finds the window on the far side.
+    pub fn wrap_around(&self, from: Rect, region: Rect) -> Rect {
+        let (fx, fy, fw, fh) = from;
+        let (rx, ry, rw, rh) = region;
+        match self {
+            Direction::Left => (rx + rw, fy, fw, fh),
+            Direction::Right => (rx - fw, fy, fw, fh),
+            Direction::Up => (fx, ry + rh, fw, fh),
+            Direction::Down => (fx, ry - fh, fw, fh),
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! The errors i4 reports, and the exit codes they map to.
+
+use i3ipc::{EstablishError, MessageError};
+use std::fmt;
+
+/// Everything that can make an i4 invocation fail.
+///
+/// Each variant maps to its own process exit code so that keybinding
+/// wrappers can tell the cases apart:
+///
+/// | Code | Variant       | Meaning                                         |
+/// |------|---------------|-------------------------------------------------|
+/// | 1    | `NoNeighbour` | There is no window in the requested direction   |
+/// | 2    |               | Invalid usage (reported by the argument parser) |
+/// | 3    | `NoFocus`     | No node in the tree is focused                  |
+/// | 4    | `Connect`     | Could not connect to the i3 IPC socket          |
+/// | 5    | `Tree`        | Could not fetch the tree, workspaces or outputs |
+/// | 6    | `Command`     | i3 rejected a command i4 sent                   |
+/// | 8    | `OffGrid`     | The focused workspace is not on the grid        |
+/// | 9    | `Daemon`      | Could not run or talk to the i4 daemon          |
+/// | 10   | `Config`      | The config file is invalid                      |
+/// | 11   | `Snapshot`    | Could not read or write a snapshot              |
+///
+/// `Remote` carries an error the daemon hit while running a command for a
+/// client, along with the exit code it would have had.
+#[derive(Debug)]
+pub enum Error {
+    /// Nothing lies in the direction, or the target, named.
+    NoNeighbour(String),
+    /// No node in the tree is focused.
+    NoFocus,
+    /// The window manager's socket could not be found or connected to.
+    Connect(EstablishError),
+    /// A request for the tree, workspaces or outputs failed.
+    Tree(MessageError),
+    /// The window manager rejected a command.
+    Command {
+        /// The command as sent.
+        command: String,
+        /// What i3 said about each part of it that failed.
+        errors: Vec<String>,
+    },
+    /// The name of the workspace that is off the grid.
+    OffGrid(String),
+    /// Running the daemon, or talking to it, failed.
+    Daemon(std::io::Error),
+    /// What is wrong with the config, and where.
+    Config(String),
+    /// What went wrong with which file.
+    Snapshot(String),
+    /// An error the daemon reported back to its client.
+    Remote {
+        /// The error as the daemon put it.
+        message: String,
+        /// The exit code it maps to.
+        code: i32,
+    },
+}
+
+/// The table of exit codes at the end of `--help`.
+pub const EXIT_CODES_HELP: &str = "Exit codes:
+  0  Success
+  1  No window in the requested direction
+  2  Invalid usage
+  3  No node in focus
+  4  Could not connect to i3
+  5  Could not fetch the tree, workspaces or outputs from i3
+  6  i3 rejected the command
+  8  The focused workspace is not on the workspace grid
+  9  Could not run or talk to the i4 daemon
+  10 The config file is invalid
+  11 Could not read or write a snapshot";
+
+impl Error {
+    /// The process exit code for this error, as in the table above.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::NoNeighbour(_) => 1,
+            Error::NoFocus => 3,
+            Error::Connect(_) => 4,
+            Error::Tree(_) => 5,
+            Error::Command { .. } => 6,
+            Error::OffGrid(_) => 8,
+            Error::Daemon(_) => 9,
+            Error::Config(_) => 10,
+            Error::Snapshot(_) => 11,
+            Error::Remote { code, .. } => *code,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NoNeighbour(target) => write!(f, "No {} node", target),
+            Error::NoFocus => write!(f, "No node in focus"),
+            Error::Connect(err) => write!(f, "Could not connect to i3: {}", err),
+            Error::Tree(err) => write!(f, "Could not query i3: {}", err),
+            Error::Command { command, errors } => {
+                write!(f, "i3 rejected `{}`", command)?;
+                if !errors.is_empty() {
+                    write!(f, ": {}", errors.join(", "))?;
+                }
+                Ok(())
+            }
+            Error::OffGrid(workspace) => {
+                write!(f, "Workspace {} is not on the workspace grid", workspace)
+            }
+            Error::Daemon(err) => write!(f, "i4 daemon: {}", err),
+            Error::Config(err) => write!(f, "Config error: {}", err),
+            Error::Snapshot(err) => write!(f, "Snapshot error: {}", err),
+            Error::Remote { message, .. } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<EstablishError> for Error {
+    fn from(err: EstablishError) -> Self {
+        Error::Connect(err)
+    }
+}
+
+impl From<MessageError> for Error {
+    fn from(err: MessageError) -> Self {
+        Error::Tree(err)
+    }
+}
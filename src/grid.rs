@@ -0,0 +1,62 @@
+//! The workspace grid that `i4 workspace` moves around.
+
+use crate::direction::Direction;
+use serde::{Deserialize, Serialize};
+
+/// Workspaces arranged as a `rows` × `columns` matrix on each output.
+///
+/// Cells are numbered row by row. Every output gets its own block of
+/// workspace numbers, so with a 3 × 3 grid the first output holds workspaces
+/// 1 to 9, the second 10 to 18, and so on. Outputs may have grids of
+/// different sizes; each block starts right after the previous one.
+///
+/// The blocks go to the outputs connected at the time, from left to right,
+/// and aren't tied to output names: unplug the leftmost output and the next
+/// one takes over workspaces 1 to 9.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Grid {
+    /// How many rows of workspaces.
+    pub rows: i32,
+    /// How many workspaces in a row.
+    pub columns: i32,
+}
+
+impl Grid {
+    /// How many workspaces the grid holds.
+    pub fn size(&self) -> i32 {
+        self.rows * self.columns
+    }
+
+    /// The (row, column) of workspace `num` in the grid starting at `first`,
+    /// or `None` if the workspace lies outside it.
+    pub fn cell(&self, first: i32, num: i32) -> Option<(i32, i32)> {
+        let offset = num - first;
+        if offset < 0 || offset >= self.size() {
+            return None;
+        }
+        Some((offset / self.columns, offset % self.columns))
+    }
+
+    /// The workspace number at `(row, column)` in the grid starting at `first`.
+    pub fn number(&self, first: i32, (row, column): (i32, i32)) -> i32 {
+        first + row * self.columns + column
+    }
+
+    /// The workspace next to workspace `num` in the given direction, or
+    /// `None` at the edge of the grid or if `num` is not on it.
+    pub fn neighbour(&self, first: i32, num: i32, direction: Direction) -> Option<i32> {
+        let (row, column) = self.cell(first, num)?;
+        let (row, column) = match direction {
+            Direction::Left => (row, column - 1),
+            Direction::Right => (row, column + 1),
+            Direction::Up => (row - 1, column),
+            Direction::Down => (row + 1, column),
+        };
+        if (0..self.rows).contains(&row) && (0..self.columns).contains(&column) {
+            Some(self.number(first, (row, column)))
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! Focus history: which windows were focused most recently.
+//!
+//! The history lives in a state file so that separate invocations share it.
+//! Every i4 command records the window focused when it runs, and the daemon
+//! records each focus change i3 reports, so the list stays accurate as long
+//! as either runs often enough.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How many windows the history remembers.
+const LENGTH: usize = 64;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct History {
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    #[serde(skip)]
+    changed: bool,
+    /// Window ids, most recently focused first.
+    windows: Vec<i64>,
+    cycle: Option<Cycle>,
+}
+
+/// An Alt-Tab style walk down the history. The order is frozen while it
+/// lasts, and only committed once no step was taken for `timeout` ms.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Cycle {
+    order: Vec<i64>,
+    position: usize,
+    /// When the last step was taken, in ms since the epoch.
+    at: u64,
+    timeout: u64,
+}
+
+/// `$XDG_STATE_HOME/i4`, falling back to `~/.local/state/i4`.
+pub fn state_dir() -> Option<PathBuf> {
+    match std::env::var_os("XDG_STATE_HOME") {
+        Some(dir) => Some(Path::new(&dir).join("i4")),
+        None => std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/state/i4")),
+    }
+}
+
+/// The history kept in the state directory, or one in memory if there is
+/// no state directory.
+pub fn load() -> History {
+    match state_dir() {
+        Some(dir) => History::load(dir.join("history.json")),
+        None => History::in_memory(),
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+impl History {
+    /// A history that is never written to disk.
+    pub fn in_memory() -> Self {
+        History::default()
+    }
+
+    /// Reads the history from `path`. A missing or unreadable file starts an
+    /// empty history, which is saved there.
+    pub fn load(path: PathBuf) -> Self {
+        let mut history: History = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        history.path = Some(path);
+        history
+    }
+
+    /// Writes the history back if anything changed.
+    pub fn save(&mut self) -> std::io::Result<()> {
+        let path = match &self.path {
+            Some(path) if self.changed => path,
+            _ => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string(self)?)?;
+        self.changed = false;
+        Ok(())
+    }
+
+    /// Notes that `id` has focus. Ignored while a cycle is under way, since
+    /// the cycle's own focus changes must not reorder what it walks through.
+    pub fn record(&mut self, id: i64, now: u64) {
+        self.settle(now);
+        if self.cycle.is_some() || self.windows.first() == Some(&id) {
+            return;
+        }
+        self.push(id);
+    }
+
+    /// Commits a cycle whose timeout has passed: the window it ended on
+    /// becomes the most recent one.
+    pub fn settle(&mut self, now: u64) {
+        let expired = match &self.cycle {
+            Some(cycle) => now.saturating_sub(cycle.at) >= cycle.timeout,
+            None => false,
+        };
+        if expired {
+            let cycle = self.cycle.take().unwrap();
+            self.push(cycle.order[cycle.position]);
+        }
+    }
+
+    fn push(&mut self, id: i64) {
+        self.windows.retain(|window| *window != id);
+        self.windows.insert(0, id);
+        self.windows.truncate(LENGTH);
+        self.changed = true;
+    }
+
+    /// The `n`th most recently focused window that still exists, counting the
+    /// focused one as 0.
+    pub fn mru(&self, n: usize, focused: i64, exists: impl Fn(i64) -> bool) -> Option<i64> {
+        self.order(focused, exists).get(n).copied()
+    }
+
+    /// Takes the next step of an Alt-Tab style cycle, starting a new one if
+    /// the last step is more than `timeout` ms ago.
+    pub fn cycle(
+        &mut self,
+        focused: i64,
+        exists: impl Fn(i64) -> bool,
+        timeout: u64,
+        now: u64,
+    ) -> Option<i64> {
+        self.settle(now);
+        let cycle = match self.cycle.take() {
+            Some(mut cycle) => {
+                cycle.position = (cycle.position + 1) % cycle.order.len();
+                cycle
+            }
+            None => {
+                let order = self.order(focused, exists);
+                if order.len() < 2 {
+                    return None;
+                }
+                Cycle {
+                    order,
+                    position: 1,
+                    at: now,
+                    timeout,
+                }
+            }
+        };
+        let id = cycle.order[cycle.position];
+        self.cycle = Some(Cycle { at: now, ..cycle });
+        self.changed = true;
+        Some(id)
+    }
+
+    /// The remembered windows that still exist, with the focused one first.
+    fn order(&self, focused: i64, exists: impl Fn(i64) -> bool) -> Vec<i64> {
+        std::iter::once(focused)
+            .chain(
+                self.windows
+                    .iter()
+                    .copied()
+                    .filter(|id| *id != focused && exists(*id)),
+            )
+            .collect()
+    }
+}
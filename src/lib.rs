@@ -0,0 +1,1070 @@
+//! i4 - a grid-like navigator for i3wm
+//!
+//! The `i4` binary is a thin wrapper around this crate, which other tools,
+//! such as status bars and launchers, can use to find their way around an i3
+//! or sway session the same way i4 does:
+//!
+//! - [`tree::Con`] is the layout tree as the window manager reports it, and
+//!   [`backend::Backend`] is where it comes from: a live session through
+//!   [`backend::connect`], or a file through [`backend::FixtureBackend`].
+//! - [`Node`] borrows a container with the path up to the root, and classifies
+//!   it as a [`NodeType`].
+//! - [`Node::get_neighbour`] and its relatives find the window in a
+//!   [`direction::Direction`], and the one before or after it.
+//! - [`grid::Grid`] lays workspaces out in rows and columns on each output.
+//!
+//! ```
+//! use i4::backend::{Backend, FixtureBackend};
+//! use i4::direction::Direction;
+//! use i4::Node;
+//!
+//! let window = |id: i64, x: i32, focused: bool| {
+//!     serde_json::json!({
+//!         "id": id, "type": "con", "window": id, "focused": focused,
+//!         "rect": { "x": x, "y": 0, "width": 960, "height": 1080 },
+//!     })
+//! };
+//! let tree = serde_json::json!({
+//!     "id": 1, "type": "root", "nodes": [{
+//!         "id": 2, "type": "output", "name": "DP-1", "focus": [3],
+//!         "rect": { "x": 0, "y": 0, "width": 1920, "height": 1080 },
+//!         "nodes": [{
+//!             "id": 3, "type": "workspace", "name": "1", "num": 1, "focus": [4, 5],
+//!             "rect": { "x": 0, "y": 0, "width": 1920, "height": 1080 },
+//!             "nodes": [window(4, 0, true), window(5, 960, false)],
+//!         }],
+//!     }],
+//! });
+//!
+//! let mut backend = FixtureBackend::from_json(&tree.to_string()).unwrap();
+//! let tree = backend.get_tree().unwrap();
+//! let root = Node::root(&tree);
+//! let focused = root.get_focused().unwrap();
+//! let right = focused.get_neighbour(Direction::Right).unwrap();
+//! assert_eq!(right.con().id, 5);
+//! assert!(focused.get_neighbour(Direction::Left).is_none());
+//! ```
+
+#![warn(missing_docs)]
+
+// First, so that every other module can use its macros.
+#[macro_use]
+mod macros;
+
+pub mod backend;
+// The command line of the `i4` binary, rather than part of the library.
+#[doc(hidden)]
+pub mod cli;
+pub mod config;
+mod daemon;
+pub mod direction;
+pub mod error;
+pub mod grid;
+mod history;
+pub mod logger;
+pub mod output;
+pub mod query;
+mod snapshot;
+#[cfg(test)]
+mod tests;
+pub mod tree;
+
+extern crate i3ipc;
+
+use backend::{Backend, DryRunBackend, FixtureBackend};
+use clap::CommandFactory;
+use cli::{
+    Cli, Command, ConfigAction, ListTarget, Navigation, SnapshotAction, Target, WorkspaceAction,
+};
+use config::Config;
+use core::fmt;
+use direction::{Direction, Rect, Scope, Wrap};
+use error::Error;
+use grid::Grid;
+use history::History;
+use i3ipc::reply::NodeLayout as I3NodeLayout;
+use i3ipc::reply::NodeType as I3NodeType;
+use i3ipc::reply::{Outputs as I3Outputs, Workspaces as I3Workspaces};
+use logger::{Filter, Level, Logger};
+use output::{FocusedRecord, NodeRecord, OutputFormat};
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::rc::Rc;
+use tree::Con;
+use I3NodeLayout::{Stacked as I3Stacked, Tabbed as I3Tabbed};
+use I3NodeType::{Output as I3Output, Workspace as I3Workspace};
+
+macro_rules! dbg_node_opt {
+    ($node:expr) => {
+        match $node {
+            Some(node) => format!("{}", node),
+            None => "None".to_string(),
+        }
+    };
+}
+
+/// A container in the layout tree, along with the chain of its ancestors, so
+/// that it can tell which workspace and output it is on and find its way to
+/// the windows around it.
+///
+/// A node borrows its container from the tree it was made from, and shares
+/// its ancestors with its siblings, so walking the tree copies nothing.
+#[derive(Clone, Debug)]
+pub struct Node<'a> {
+    current: &'a Con,
+    parent: Option<Rc<Node<'a>>>,
+}
+
+/// What a node is to i4, which is not always what i3 calls it: a plain
+/// container is a window when it holds one and a split otherwise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeType {
+    /// The root of the tree, above the outputs.
+    Root,
+    /// A monitor, or i3's internal `__i3` output holding the scratchpad.
+    Output,
+    /// The area of an output that holds docked windows, such as bars.
+    DockArea,
+    /// A workspace, holding the tiled and floating containers on it.
+    Workspace,
+    /// A container that arranges its children, rather than holding a window.
+    Split,
+    /// The container i3 wraps a floating window in.
+    FloatingContainer,
+    /// A container holding a client window.
+    Window,
+    /// A type i4 does not know.
+    Unknown,
+}
+
+impl NodeType {
+    /// The stable, uncoloured name used in machine-readable output.
+    pub fn name(&self) -> &'static str {
+        match self {
+            NodeType::Root => "root",
+            NodeType::Output => "output",
+            NodeType::DockArea => "dockarea",
+            NodeType::Workspace => "workspace",
+            NodeType::Split => "split",
+            NodeType::FloatingContainer => "floating_container",
+            NodeType::Window => "window",
+            NodeType::Unknown => "unknown",
+        }
+    }
+}
+
+/// Styled for stdout; the alternate form, `{:#}`, is plain, for the log.
+impl std::fmt::Display for NodeType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let colours = config::colours();
+        let (colour, label) = match self {
+            NodeType::Root => (&colours.root, "Root"),
+            NodeType::Output => (&colours.output, "Output"),
+            NodeType::DockArea => (&colours.dockarea, "DockArea"),
+            NodeType::Workspace => (&colours.workspace, "Workspace"),
+            NodeType::Split => (&colours.split, "Split"),
+            NodeType::Window => (&colours.window, "Window"),
+            NodeType::FloatingContainer => (&colours.floating_container, "FloatingContainer"),
+            NodeType::Unknown => (&colours.unknown, "Unknown"),
+        };
+        if f.alternate() {
+            write!(f, "{}", label)
+        } else {
+            write!(f, "{}", style!(colour, "{}", label))
+        }
+    }
+}
+
+impl<'a> Node<'a> {
+    fn new(node: &'a Con, parent: Option<Rc<Node<'a>>>) -> Self {
+        Node {
+            current: node,
+            parent,
+        }
+    }
+
+    /// The root of a tree, as `Backend::get_tree` returns it.
+    pub fn root(tree: &'a Con) -> Self {
+        Node::new(tree, None)
+    }
+
+    /// The container itself.
+    pub fn con(&self) -> &'a Con {
+        self.current
+    }
+
+    /// Draws the subtree below this node, one node per line, the way
+    /// `i4 list all` does.
+    pub fn to_tree_string(&self) -> String {
+        fn to_string_tree(
+            node: &Node,
+            depth: usize,
+            is_last: bool,
+            mut tree_string: String,
+        ) -> String {
+            let indent = if depth == 0 {
+                String::new()
+            } else {
+                let prefix = if is_last { "    " } else { "│   " };
+                format!(
+                    "{}{}",
+                    prefix.repeat(depth - 1),
+                    if is_last { "└── " } else { "├── " }
+                )
+            };
+
+            let mut node_info = format!(
+                "{}\t{:?} {:?}",
+                node.current.id, node.current.name, node.current.rect
+            );
+            if node.current.focused {
+                node_info = style!("bold,white", "{}", node_info);
+            } else {
+                node_info = style!("dim,white", "{}", node_info);
+            }
+            let children: Vec<_> = node
+                .children()
+                .into_iter()
+                .chain(node.floating_children())
+                .collect();
+            tree_string = format!(
+                "{}{}[{}] {}\n",
+                tree_string,
+                indent,
+                node.get_node_type(),
+                node_info
+            );
+            for (i, child) in children.iter().enumerate() {
+                tree_string =
+                    to_string_tree(child, depth + 1, i == children.len() - 1, tree_string);
+            }
+            tree_string
+        }
+
+        let tree_string = String::new();
+        to_string_tree(self, 0, true, tree_string)
+    }
+
+    /// What kind of node this is.
+    pub fn get_node_type(&self) -> NodeType {
+        match self.current.nodetype {
+            I3NodeType::Root => NodeType::Root,
+            I3NodeType::Output => NodeType::Output,
+            I3NodeType::Workspace => NodeType::Workspace,
+            I3NodeType::Con => {
+                if self.current.is_window() {
+                    NodeType::Window
+                } else {
+                    NodeType::Split
+                }
+            }
+            I3NodeType::DockArea => NodeType::DockArea,
+            I3NodeType::FloatingCon if self.current.is_window() => NodeType::Window,
+            I3NodeType::FloatingCon => NodeType::FloatingContainer,
+            I3NodeType::Unknown => NodeType::Unknown,
+        }
+    }
+
+    /// The tiled containers directly below this one.
+    pub fn children(&self) -> Vec<Node<'a>> {
+        let parent = Rc::new(self.clone());
+        self.current
+            .nodes
+            .iter()
+            .map(|child_node| Node::new(child_node, Some(Rc::clone(&parent))))
+            .collect()
+    }
+
+    /// The floating containers directly below this one, which i3 keeps
+    /// apart from the tiled ones. Navigation leaves them out.
+    pub fn floating_children(&self) -> Vec<Node<'a>> {
+        let parent = Rc::new(self.clone());
+        self.current
+            .floating_nodes
+            .iter()
+            .map(|child_node| Node::new(child_node, Some(Rc::clone(&parent))))
+            .collect()
+    }
+
+    /// The workspace this node is on, or the node itself if it is one.
+    pub fn get_parent_workspace(&self) -> Option<Node<'a>> {
+        if self.current.nodetype == I3Workspace {
+            return Some(self.clone());
+        }
+
+        if let Some(parent) = &self.parent {
+            return parent.get_parent_workspace();
+        }
+
+        None
+    }
+
+    /// The output this node is on, or the node itself if it is one.
+    pub fn get_parent_output(&self) -> Option<Node<'a>> {
+        if self.current.nodetype == I3Output {
+            return Some(self.clone());
+        }
+
+        if let Some(parent) = &self.parent {
+            return parent.get_parent_output();
+        }
+
+        None
+    }
+
+    /// The focused node in this subtree, which may be a floating window.
+    pub fn get_focused(&self) -> Option<Node<'a>> {
+        if self.current.focused {
+            return Some(self.clone());
+        }
+
+        for child in self.children().into_iter().chain(self.floating_children()) {
+            if let Some(focused_child) = child.get_focused() {
+                return Some(focused_child);
+            }
+        }
+
+        None
+    }
+
+    /// The outputs below the root, leaving out i3's internal `__i3` output
+    /// that holds the scratchpad.
+    pub fn get_outputs(&self) -> Vec<Node<'a>> {
+        self.children()
+            .into_iter()
+            .filter(|node| {
+                node.current.nodetype == I3Output && node.current.name.as_deref() != Some("__i3")
+            })
+            .collect()
+    }
+
+    /// The tiled windows in this subtree, in tree order. These are the ones
+    /// navigation moves between.
+    pub fn get_windows(&self) -> Vec<Node<'a>> {
+        let mut windows = Vec::new();
+        fn collect_windows<'a>(node: &Node<'a>, windows: &mut Vec<Node<'a>>) {
+            if let NodeType::Window = node.get_node_type() {
+                windows.push(node.clone());
+            }
+            for child in node.children() {
+                collect_windows(&child, windows);
+            }
+        }
+        collect_windows(self, &mut windows);
+        windows
+    }
+
+    /// Like `get_windows`, but with floating windows too.
+    pub fn get_all_windows(&self) -> Vec<Node<'a>> {
+        let mut windows = Vec::new();
+        fn collect_windows<'a>(node: &Node<'a>, windows: &mut Vec<Node<'a>>) {
+            if let NodeType::Window = node.get_node_type() {
+                windows.push(node.clone());
+            }
+            for child in node.children().iter().chain(&node.floating_children()) {
+                collect_windows(child, windows);
+            }
+        }
+        collect_windows(self, &mut windows);
+        windows
+    }
+
+    /// The workspace grid of the output named `output`, as `grid_for` sizes
+    /// each output's grid, and the number of its first workspace. The outputs
+    /// connected now take their blocks of workspace numbers from left to
+    /// right, so connecting or disconnecting one renumbers the blocks of
+    /// those to its right.
+    pub fn get_workspace_grid(
+        &self,
+        output: &str,
+        grid_for: impl Fn(&str) -> Grid,
+    ) -> Option<(Grid, i32)> {
+        let mut outputs = self.get_root().get_outputs();
+        outputs.sort_by_key(|output| (output.current.rect.0, output.current.rect.1));
+        let mut first = 1;
+        for node in &outputs {
+            let name = node.current.name.as_deref().unwrap_or_default();
+            let grid = grid_for(name);
+            if name == output {
+                return Some((grid, first));
+            }
+            first += grid.size();
+        }
+        None
+    }
+
+    /// Whether this node floats, by itself or inside a floating container.
+    pub fn is_floating(&self) -> bool {
+        let mut current = Some(self);
+        while let Some(node) = current {
+            if node.current.floating {
+                return true;
+            }
+            current = node.parent();
+        }
+        false
+    }
+
+    /// A node is hidden when one of its ancestors is a tabbed or stacked
+    /// container showing a different child, or a workspace that its output
+    /// is not currently showing. Floating containers are drawn on top of
+    /// their workspace's tabs, so its layout doesn't hide them.
+    pub fn is_hidden(&self) -> bool {
+        let mut current = self;
+        while let Some(parent) = current.parent() {
+            let layout = &parent.current.layout;
+            let shows_one_child = match current.current.nodetype {
+                I3Workspace => true,
+                I3NodeType::FloatingCon => false,
+                _ => *layout == I3Tabbed || *layout == I3Stacked,
+            };
+            if shows_one_child && parent.current.focus.first() != Some(&current.current.id) {
+                return true;
+            }
+            current = parent;
+        }
+        false
+    }
+
+    /// Collects the windows that are actually on screen: those on a workspace
+    /// currently shown on an active output and not tucked behind another tab.
+    /// The scratchpad lives on the hidden `__i3` output, so it never matches.
+    pub fn get_visible_windows(
+        &self,
+        workspaces: &I3Workspaces,
+        outputs: &I3Outputs,
+    ) -> Vec<Node<'a>> {
+        let visible_workspaces: Vec<(&str, &str)> = workspaces
+            .workspaces
+            .iter()
+            .filter(|workspace| {
+                workspace.visible
+                    && outputs.outputs.iter().any(|output| {
+                        output.active
+                            && output.name == workspace.output
+                            && output.current_workspace.as_deref() == Some(&workspace.name)
+                    })
+            })
+            .map(|workspace| (workspace.name.as_str(), workspace.output.as_str()))
+            .collect();
+
+        self.get_all_windows()
+            .into_iter()
+            .filter(|window| {
+                let workspace = window
+                    .get_parent_workspace()
+                    .and_then(|workspace| workspace.current.name.clone());
+                let output = window
+                    .get_parent_output()
+                    .and_then(|output| output.current.name.clone());
+                match (workspace, output) {
+                    (Some(workspace), Some(output)) => {
+                        visible_workspaces.contains(&(workspace.as_str(), output.as_str()))
+                    }
+                    _ => false,
+                }
+            })
+            .filter(|window| !window.is_hidden())
+            .collect()
+    }
+
+    /// Finds the window next to this node in the given direction by comparing
+    /// the rects of the visible windows on the same workspace, regardless of
+    /// which split container they live in. At the edge of the workspace the
+    /// search continues on the adjacent output.
+    pub fn get_neighbour(&self, direction: Direction) -> Option<Node<'a>> {
+        let workspace = self.get_parent_workspace()?;
+        let candidates = self.get_candidates(&workspace);
+        let rects: Vec<Rect> = candidates
+            .iter()
+            .map(|window| window.current.rect)
+            .collect();
+        match direction.nearest(self.current.rect, &rects) {
+            Some(i) => Some(candidates[i].clone()),
+            None => self.get_neighbour_on_adjacent_output(direction),
+        }
+    }
+
+    /// Picks the window on the output next to this node's one. Outputs are
+    /// matched by the rects of their `Output` nodes, so monitors mounted at
+    /// different heights still line up, and a window that faces none of the
+    /// windows there goes to the one closest to it across the edge.
+    fn get_neighbour_on_adjacent_output(&self, direction: Direction) -> Option<Node<'a>> {
+        let output = self.get_parent_output()?;
+        let outputs: Vec<Node<'a>> = self
+            .get_root()
+            .get_outputs()
+            .into_iter()
+            .filter(|node| node.current.id != output.current.id)
+            .collect();
+        let rects: Vec<Rect> = outputs.iter().map(|output| output.current.rect).collect();
+        let adjacent = &outputs[direction.closest(output.current.rect, &rects)?];
+
+        let candidates = self.get_candidates(adjacent);
+        let rects: Vec<Rect> = candidates
+            .iter()
+            .map(|window| window.current.rect)
+            .collect();
+        direction
+            .closest(self.current.rect, &rects)
+            .map(|i| candidates[i].clone())
+    }
+
+    /// Like `get_neighbour`, but when nothing lies in that direction the
+    /// search starts over from the opposite edge of the `wrap` region. With
+    /// `Wrap::Output` or `Wrap::Global` windows on the other outputs' visible
+    /// workspaces are candidates too.
+    pub fn get_neighbour_wrapping(&self, direction: Direction, wrap: Wrap) -> Option<Node<'a>> {
+        let region = match self.get_wrap_region(wrap) {
+            Some(region) => region,
+            None => return self.get_neighbour(direction),
+        };
+        let candidates = self.get_candidates(&region);
+        let rects: Vec<Rect> = candidates
+            .iter()
+            .map(|window| window.current.rect)
+            .collect();
+        let from = self.current.rect;
+        direction
+            .nearest(from, &rects)
+            .or_else(|| {
+                let wrapped = direction.wrap_around(from, region.current.rect);
+                direction.nearest(wrapped, &rects)
+            })
+            .map(|i| candidates[i].clone())
+    }
+
+    /// The windows in `region` this node could move to: the visible ones,
+    /// other than itself.
+    fn get_candidates(&self, region: &Node<'a>) -> Vec<Node<'a>> {
+        region
+            .get_windows()
+            .into_iter()
+            .filter(|window| window.current.id != self.current.id && !window.is_hidden())
+            .collect()
+    }
+
+    /// The window before this one in the whole tree, skipping workspaces that
+    /// aren't showing.
+    pub fn previous_window(&self) -> Option<Node<'a>> {
+        self.previous_window_within(&self.get_root())
+    }
+
+    /// The window after this one in the whole tree, skipping workspaces that
+    /// aren't showing.
+    pub fn next_window(&self) -> Option<Node<'a>> {
+        self.next_window_within(&self.get_root())
+    }
+
+    /// The tiled windows under this node on workspaces their output is
+    /// showing, which is where sequential navigation may go.
+    fn get_shown_windows(&self) -> Vec<Node<'a>> {
+        self.get_windows()
+            .into_iter()
+            .filter(|window| !window.is_on_hidden_workspace())
+            .collect()
+    }
+
+    /// Whether this node is on a workspace its output isn't showing.
+    fn is_on_hidden_workspace(&self) -> bool {
+        let mut current = self;
+        while let Some(parent) = current.parent() {
+            if current.current.nodetype == I3Workspace {
+                return parent.current.focus.first() != Some(&current.current.id);
+            }
+            current = parent;
+        }
+        false
+    }
+
+    /// Walks back through the tree from this node, without leaving `region`.
+    fn previous_window_within(&self, region: &Node) -> Option<Node<'a>> {
+        let mut current = self.clone();
+
+        while current.current.id != region.current.id {
+            let parent = match current.parent() {
+                Some(parent) => parent.clone(),
+                None => break,
+            };
+            let siblings = parent.children();
+            if let Some(i) = siblings
+                .iter()
+                .position(|sibling| sibling.current.id == current.current.id)
+            {
+                // Skip over siblings without windows, such as empty docks.
+                for sibling in siblings[..i].iter().rev() {
+                    if let Some(window) = sibling.get_shown_windows().pop() {
+                        return Some(window);
+                    }
+                }
+            }
+            current = parent;
+        }
+
+        None
+    }
+
+    /// Walks on through the tree from this node, without leaving `region`.
+    fn next_window_within(&self, region: &Node) -> Option<Node<'a>> {
+        let mut current = self.clone();
+
+        while current.current.id != region.current.id {
+            let parent = match current.parent() {
+                Some(parent) => parent.clone(),
+                None => break,
+            };
+            let siblings = parent.children();
+            if let Some(i) = siblings
+                .iter()
+                .position(|sibling| sibling.current.id == current.current.id)
+            {
+                for sibling in &siblings[i + 1..] {
+                    if let Some(window) = sibling.get_shown_windows().into_iter().next() {
+                        return Some(window);
+                    }
+                }
+            }
+            current = parent;
+        }
+
+        None
+    }
+
+    /// The window before this one within `scope`. When `wrap` is set it
+    /// takes over from `scope`: the walk goes round to the last window of the
+    /// wrap region instead of stopping.
+    pub fn previous_window_in(&self, scope: Scope, wrap: Wrap) -> Option<Node<'a>> {
+        match self.get_wrap_region(wrap) {
+            Some(region) => self
+                .previous_window_within(&region)
+                .or_else(|| region.get_shown_windows().pop())
+                .filter(|window| window.current.id != self.current.id),
+            None => self.previous_window_within(&self.get_scope_region(scope)?),
+        }
+    }
+
+    /// The window after this one within `scope`. When `wrap` is set it takes
+    /// over from `scope`: the walk goes round to the first window of the wrap
+    /// region instead of stopping.
+    pub fn next_window_in(&self, scope: Scope, wrap: Wrap) -> Option<Node<'a>> {
+        match self.get_wrap_region(wrap) {
+            Some(region) => self
+                .next_window_within(&region)
+                .or_else(|| region.get_shown_windows().into_iter().next())
+                .filter(|window| window.current.id != self.current.id),
+            None => self.next_window_within(&self.get_scope_region(scope)?),
+        }
+    }
+
+    /// The container sequential navigation is bounded to.
+    fn get_scope_region(&self, scope: Scope) -> Option<Node<'a>> {
+        match scope {
+            Scope::Container => self.parent().cloned(),
+            Scope::Workspace => self.get_parent_workspace(),
+            Scope::Output => self.get_parent_output(),
+            Scope::All => Some(self.get_root()),
+        }
+    }
+
+    /// The container `wrap` cycles within, or `None` when it doesn't wrap.
+    fn get_wrap_region(&self, wrap: Wrap) -> Option<Node<'a>> {
+        match wrap {
+            Wrap::None => None,
+            Wrap::Workspace => self.get_parent_workspace(),
+            Wrap::Output => self.get_parent_output(),
+            Wrap::Global => Some(self.get_root()),
+        }
+    }
+
+    /// The root of the tree this node is in.
+    pub fn get_root(&self) -> Node<'a> {
+        match self.parent() {
+            Some(parent) => parent.get_root(),
+            None => self.clone(),
+        }
+    }
+
+    /// The node directly above this one, or `None` for the root.
+    pub fn parent(&self) -> Option<&Node<'a>> {
+        self.parent.as_deref()
+    }
+}
+
+/// Like `NodeType`, styled for stdout unless written as `{:#}`.
+impl fmt::Display for Node<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let node_type = if f.alternate() {
+            format!("{:#}", self.get_node_type())
+        } else {
+            self.get_node_type().to_string()
+        };
+        let node_id = self.current.id;
+        let node_name = match self.current.name.as_ref() {
+            Some(name) => String::from(name),
+            None => "".to_string(),
+        };
+        let parent_workspace = match self.get_parent_workspace() {
+            Some(parent) => match parent.current.name.clone() {
+                Some(name) => name,
+                None => "".to_string(),
+            },
+            None => "".to_string(),
+        };
+        let parent_output = match self.get_parent_output() {
+            Some(parent) => match parent.current.name.clone() {
+                Some(name) => name,
+                None => "".to_string(),
+            },
+            None => "".to_string(),
+        };
+
+        write!(
+            f,
+            "[{}] {} \"{}\" {:?} {} {}",
+            node_type, node_id, node_name, self.current.rect, parent_workspace, parent_output
+        )
+    }
+}
+
+/// Renders nodes as the result of a `list` command: one per line as text,
+/// or as a single document in the machine-readable formats.
+fn render_nodes(format: OutputFormat, nodes: &[Node]) -> String {
+    match format {
+        OutputFormat::Text => nodes
+            .iter()
+            .map(|node| node.to_string())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => {
+            let records: Vec<NodeRecord> = nodes.iter().map(NodeRecord::new).collect();
+            output::render_nodes(format, &records)
+        }
+    }
+}
+
+/// Resolves the window a `focus` or `move` target refers to.
+fn get_target<'a>(
+    focused_node: &Node<'a>,
+    navigation: &Navigation,
+    history: &mut History,
+) -> Option<Node<'a>> {
+    let Navigation {
+        target,
+        n,
+        scope,
+        wrap,
+        timeout,
+    } = *navigation;
+    let focused = focused_node.current.id;
+    match target {
+        Target::Previous => focused_node.previous_window_in(scope, wrap),
+        Target::Next => focused_node.next_window_in(scope, wrap),
+        Target::Back | Target::Mru | Target::MruCycle => {
+            // The history may name windows that have closed since.
+            let windows = focused_node.get_root().get_all_windows();
+            let ids: HashSet<i64> = windows.iter().map(|window| window.current.id).collect();
+            let exists = |id: i64| ids.contains(&id);
+            let id = match target {
+                Target::Back => history.mru(1, focused, exists),
+                Target::Mru => history.mru(n.unwrap_or(1), focused, exists),
+                _ => history.cycle(focused, exists, timeout, history::now()),
+            }?;
+            windows.into_iter().find(|window| window.current.id == id)
+        }
+        _ => focused_node.get_neighbour_wrapping(target.direction()?, wrap),
+    }
+}
+
+/// Runs the command line `cli` the way the `i4` binary does, printing its
+/// result. `args` are the arguments `cli` was parsed from, without the
+/// program name, which a running daemon gets to parse again. `config` is the
+/// loaded config file, or why it couldn't be loaded, which only
+/// `config check` and `completions` can do without.
+pub fn run(args: &[OsString], cli: Cli, config: Result<Config, Error>) -> Result<(), Error> {
+    let format = cli.format;
+
+    match cli.command {
+        Command::Completions { shell } => {
+            clap_complete::generate(shell, &mut Cli::command(), "i4", &mut std::io::stdout());
+            return Ok(());
+        }
+        Command::Config {
+            action: ConfigAction::Check { file },
+        } => {
+            println!("{}", config::check(file, format)?);
+            return Ok(());
+        }
+        _ => {}
+    }
+    let config = config?;
+    config::set_colours(config.colours.clone());
+
+    // `RUST_LOG` sets the levels, `-v` and `-q` move them up and down.
+    let mut filter = Filter::parse(&std::env::var("RUST_LOG").unwrap_or_default());
+    filter.shift(cli.verbose as i32 - cli.quiet as i32);
+    if cli.debug || config.log.debug {
+        filter.raise_to(Level::Debug);
+    }
+    let logfile = match &cli.log_file {
+        Some(path) => Some(path.clone()),
+        None if cli.debug || config.log.debug => {
+            config.log.file.clone().or_else(logger::default_path)
+        }
+        None => None,
+    };
+    match logfile {
+        // Not being able to log is no reason not to run the command.
+        Some(path) => match Logger::to_file(filter.clone(), &path, config.log.rotation()) {
+            Ok(logger) => logger::init(logger),
+            Err(err) => {
+                logger::init(Logger::new(filter));
+                warning!(
+                    "Could not open {}, logging here instead: {}",
+                    path.display(),
+                    err
+                );
+            }
+        },
+        None => logger::init(Logger::new(filter)),
+    }
+
+    if let Command::Daemon = cli.command {
+        return daemon::run(&daemon::socket_path(), config);
+    }
+    // A tree file stands in for i3, so the daemon has nothing to add, and
+    // a snapshot is written where the client runs.
+    if cli.tree_file.is_none() && !matches!(cli.command, Command::Snapshot { .. }) {
+        if let Some(output) = daemon::forward(&daemon::socket_path(), args)? {
+            if !output.is_empty() {
+                println!("{}", output);
+            }
+            return Ok(());
+        }
+    }
+
+    // Someone else's layout is no part of the local focus history.
+    let (mut backend, mut history): (Box<dyn Backend>, History) = match &cli.tree_file {
+        Some(path) => (
+            Box::new(FixtureBackend::load(path).map_err(Error::Snapshot)?),
+            History::in_memory(),
+        ),
+        None => (backend::connect()?, history::load()),
+    };
+    if let Command::Snapshot {
+        action: SnapshotAction::Save { file, anonymize },
+    } = &cli.command
+    {
+        let output = snapshot::save(backend.as_mut(), file, *anonymize)?;
+        if !output.is_empty() {
+            println!("{}", output);
+        }
+        return Ok(());
+    }
+    // There is no i3 behind a tree file to run commands, so show them.
+    let cli = Cli {
+        dry_run: cli.dry_run || cli.tree_file.is_some(),
+        ..cli
+    };
+    let result = execute_cli(cli, backend.as_mut(), &mut history, &config);
+    if let Err(err) = history.save() {
+        warning!("Could not save the focus history: {}", err);
+    }
+    let output = result?;
+    if !output.is_empty() {
+        println!("{}", output);
+    }
+    Ok(())
+}
+
+/// Runs a parsed command line. With `--dry-run` the i3 commands it would
+/// have run are printed after its output instead, and the focus history is
+/// left as it was.
+fn execute_cli(
+    cli: Cli,
+    backend: &mut dyn Backend,
+    history: &mut History,
+    config: &Config,
+) -> Result<String, Error> {
+    if !cli.dry_run {
+        return execute(cli.command, cli.format, backend, history, config);
+    }
+    let mut backend = DryRunBackend::new(backend);
+    let output = execute(
+        cli.command,
+        cli.format,
+        &mut backend,
+        &mut history.clone(),
+        config,
+    )?;
+    Ok(std::iter::once(output)
+        .filter(|output| !output.is_empty())
+        .chain(backend.commands)
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// Runs a single i4 command against a backend and returns what it prints:
+/// the result of `list` and `get` in the requested format, or nothing for
+/// commands that only act on i3.
+fn execute(
+    command: Command,
+    format: OutputFormat,
+    backend: &mut dyn Backend,
+    history: &mut History,
+    config: &Config,
+) -> Result<String, Error> {
+    let i3tree = backend.get_tree()?;
+    let root_node = Node::new(&i3tree, None);
+    // Catch up with focus changes made since i4 last ran.
+    if let Some(focused_node) = root_node.get_focused() {
+        if focused_node.current.is_window() {
+            history.record(focused_node.current.id, history::now());
+        }
+    }
+
+    match command {
+        Command::List { target, filter } => match target {
+            ListTarget::All => {
+                debug!("Listing all nodes...");
+                match format {
+                    OutputFormat::Text => Ok(root_node.to_tree_string().trim_end().to_string()),
+                    _ => Ok(output::render_node(format, &NodeRecord::tree(&root_node))),
+                }
+            }
+            ListTarget::Focused => {
+                debug!("Listing focused node...");
+                let focused_node = root_node.get_focused().ok_or(Error::NoFocus)?;
+                match format {
+                    OutputFormat::Json => {
+                        let record = FocusedRecord::new(&focused_node);
+                        return Ok(serde_json::to_string(&record).unwrap());
+                    }
+                    // A TSV row has no room for the neighbours.
+                    OutputFormat::Tsv => {
+                        let record = NodeRecord::new(&focused_node);
+                        return Ok(output::render_node(format, &record));
+                    }
+                    OutputFormat::Text => {}
+                }
+                let parent_workspace = focused_node.get_parent_workspace();
+                let parent_output = focused_node.get_parent_output();
+                Ok([
+                    format!("Focused node: {}", focused_node),
+                    format!(" | Parent workspace: {}", dbg_node_opt!(parent_workspace)),
+                    format!(" | Parent output: {}", dbg_node_opt!(parent_output)),
+                    format!(
+                        " | Previous window: {}",
+                        dbg_node_opt!(focused_node.previous_window())
+                    ),
+                    format!(
+                        " | Next node: {}",
+                        dbg_node_opt!(focused_node.next_window())
+                    ),
+                ]
+                .join("\n"))
+            }
+            ListTarget::Visible => {
+                debug!("Listing visible nodes...");
+                let workspaces = backend.get_workspaces()?;
+                let outputs = backend.get_outputs()?;
+                let mut windows = root_node.get_visible_windows(&workspaces, &outputs);
+                if let Some(filter) = filter {
+                    windows.retain(|window| filter.matches(window));
+                }
+                Ok(render_nodes(format, &windows))
+            }
+            ListTarget::Windows => {
+                debug!("Listing windows...");
+                let mut windows = root_node.get_all_windows();
+                if let Some(filter) = filter {
+                    windows.retain(|window| filter.matches(window));
+                }
+                Ok(render_nodes(format, &windows))
+            }
+        },
+        Command::Get { direction, wrap } => {
+            let focused_node = root_node.get_focused().ok_or(Error::NoFocus)?;
+            let neighbour = focused_node
+                .get_neighbour_wrapping(direction, wrap)
+                .ok_or_else(|| Error::NoNeighbour(direction.to_string()))?;
+            match format {
+                OutputFormat::Text => Ok(neighbour.to_string()),
+                _ => Ok(output::render_node(format, &NodeRecord::new(&neighbour))),
+            }
+        }
+        Command::Focus { navigation } => {
+            let target = navigation.target;
+            let focused_node = root_node.get_focused().ok_or(Error::NoFocus)?;
+            debug!("Focused node: {:#}", focused_node);
+            let target_node = get_target(&focused_node, &navigation, history)
+                .ok_or_else(|| Error::NoNeighbour(target.to_string()))?;
+            debug!("{} node: {:#}", target, target_node);
+            backend.run_command(&format!("[con_id={}] focus", target_node.current.id))?;
+            history.record(target_node.current.id, history::now());
+            Ok(String::new())
+        }
+        Command::Move { navigation } => {
+            let target = navigation.target;
+            let focused_node = root_node.get_focused().ok_or(Error::NoFocus)?;
+            debug!("Focused node: {:#}", focused_node);
+            let target_node = get_target(&focused_node, &navigation, history)
+                .ok_or_else(|| Error::NoNeighbour(target.to_string()))?;
+            debug!("Swapping with node: {:#}", target_node);
+            // Swapping rather than i3's own `move` keeps both windows'
+            // sizes and lands the window exactly where the target was,
+            // even when the two live in different split containers.
+            backend.run_command(&format!(
+                "[con_id={}] swap container with con_id {}",
+                focused_node.current.id, target_node.current.id
+            ))?;
+            Ok(String::new())
+        }
+        Command::Workspace {
+            rows,
+            columns,
+            action,
+        } => {
+            let workspaces = backend.get_workspaces()?;
+            let current = workspaces
+                .workspaces
+                .iter()
+                .find(|workspace| workspace.focused)
+                .ok_or(Error::NoFocus)?;
+            let (grid, first) = root_node
+                .get_workspace_grid(&current.output, |name| {
+                    let default = config.grid.for_output(name);
+                    Grid {
+                        rows: rows.unwrap_or(default.rows),
+                        columns: columns.unwrap_or(default.columns),
+                    }
+                })
+                .ok_or_else(|| Error::OffGrid(current.name.clone()))?;
+            if grid.cell(first, current.num).is_none() {
+                return Err(Error::OffGrid(current.name.clone()));
+            }
+
+            let direction = action.direction();
+            let target = grid
+                .neighbour(first, current.num, direction)
+                .ok_or_else(|| Error::NoNeighbour(direction.to_string()))?;
+            debug!(
+                "Workspace {} -> {} ({} of {})",
+                current.num, target, direction, current.output
+            );
+            // `workspace number` creates the workspace if it doesn't exist.
+            let command = match action {
+                WorkspaceAction::Move { .. } => format!(
+                    "move container to workspace number {0}; workspace number {0}",
+                    target
+                ),
+                _ => format!("workspace number {}", target),
+            };
+            backend.run_command(&command)?;
+            Ok(String::new())
+        }
+        Command::Daemon
+        | Command::Config { .. }
+        | Command::Snapshot { .. }
+        | Command::Completions { .. } => unreachable!(),
+    }
+}
@@ -1,59 +1,333 @@
-// use std::fs::OpenOptions;
-// use std::io::Write;
-// use std::sync::Mutex;
-
-// pub struct Logger {
-//     file: Mutex<std::fs::File>,
-// }
-
-// impl Logger {
-//     pub fn new(file_path: &str) -> Logger {
-//         let file = OpenOptions::new()
-//             .create(true)
-//             .append(true)
-//             .open(file_path)
-//             .unwrap();
-//         Logger {
-//             file: Mutex::new(file),
-//         }
-//     }
-
-//     pub fn log(&self, msg: &str) {
-//         if let Ok(mut file) = self.file.lock() {
-//             writeln!(file, "{}", msg).unwrap();
-//         }
-//     }
-// }
-
-use std::fs::OpenOptions;
-use std::io::Write;
-use std::sync::Mutex;
+//! The one logger behind the `trace!` … `error!` macros in `macros.rs`.
+//!
+//! Which messages get through is decided by a `Filter` in `RUST_LOG` syntax,
+//! parsed once at startup and then shifted by `-v` and `-q`. Each line
+//! carries a UTC timestamp, its level and the module it came from.
+//!
+//! Messages go to stderr, or with `--debug` to a log file, by default
+//! `$XDG_STATE_HOME/i4/i4.log`. The file is rotated by size.
 
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Message levels, most severe first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// Something failed.
+    Error = 1,
+    /// Something went wrong, but i4 carries on.
+    Warn,
+    /// What i4 did.
+    Info,
+    /// How it decided.
+    Debug,
+    /// Every step along the way.
+    Trace,
+}
+
+const LEVELS: [Level; 5] = [
+    Level::Error,
+    Level::Warn,
+    Level::Info,
+    Level::Debug,
+    Level::Trace,
+];
+
+impl Level {
+    fn parse(name: &str) -> Option<Level> {
+        LEVELS
+            .into_iter()
+            .find(|level| level.name().eq_ignore_ascii_case(name))
+            .or_else(|| name.eq_ignore_ascii_case("warning").then_some(Level::Warn))
+    }
+
+    /// The lowercase name, as in `RUST_LOG`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        }
+    }
+
+    fn style(&self) -> &'static str {
+        match self {
+            Level::Error => "red",
+            Level::Warn => "yellow",
+            Level::Info => "cyan",
+            Level::Debug => "magenta",
+            Level::Trace => "dim,magenta",
+        }
+    }
+}
+
+/// The most verbose level let through, 0 meaning nothing at all.
+type Verbosity = u8;
+
+const OFF: Verbosity = 0;
+
+fn verbosity(name: &str) -> Option<Verbosity> {
+    if name.eq_ignore_ascii_case("off") {
+        return Some(OFF);
+    }
+    Level::parse(name).map(|level| level as Verbosity)
+}
+
+/// A `RUST_LOG` style filter: a default level plus per-module levels, as in
+/// `warn,i4::daemon=debug`. A bare module name lets everything from it
+/// through. The longest matching module prefix wins.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Filter {
+    default: Verbosity,
+    modules: Vec<(String, Verbosity)>,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter {
+            default: Level::Warn as Verbosity,
+            modules: Vec::new(),
+        }
+    }
+}
+
+impl Filter {
+    /// Parses a filter, skipping directives it doesn't understand the way
+    /// `RUST_LOG` consumers usually do.
+    pub fn parse(spec: &str) -> Filter {
+        let mut filter = Filter::default();
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((module, level)) => {
+                    if let Some(level) = verbosity(level.trim()) {
+                        filter.modules.push((module.trim().to_string(), level));
+                    }
+                }
+                None => match verbosity(directive) {
+                    Some(level) => filter.default = level,
+                    None => filter
+                        .modules
+                        .push((directive.to_string(), Level::Trace as Verbosity)),
+                },
+            }
+        }
+        filter
+            .modules
+            .sort_by_key(|(module, _)| std::cmp::Reverse(module.len()));
+        filter
+    }
+
+    /// Makes every level more (positive `steps`) or less verbose, as `-v`
+    /// and `-q` do.
+    pub fn shift(&mut self, steps: i32) {
+        let shift = |level: &mut Verbosity| {
+            *level = (*level as i32 + steps).clamp(OFF as i32, Level::Trace as i32) as Verbosity;
+        };
+        shift(&mut self.default);
+        self.modules.iter_mut().for_each(|(_, level)| shift(level));
+    }
+
+    /// Lets at least `level` through everywhere.
+    pub fn raise_to(&mut self, level: Level) {
+        let level = level as Verbosity;
+        self.default = self.default.max(level);
+        self.modules
+            .iter_mut()
+            .for_each(|(_, verbosity)| *verbosity = (*verbosity).max(level));
+    }
+
+    /// Whether a message at `level` from the module `target` gets through.
+    pub fn enabled(&self, level: Level, target: &str) -> bool {
+        let verbosity = self
+            .modules
+            .iter()
+            .find(|(module, _)| {
+                target == module
+                    || target
+                        .strip_prefix(module.as_str())
+                        .is_some_and(|rest| rest.starts_with("::"))
+            })
+            .map(|(_, verbosity)| *verbosity)
+            .unwrap_or(self.default);
+        level as Verbosity <= verbosity
+    }
+}
+
+/// When a log file gets rotated: once it would grow past `max_size` bytes
+/// it is renamed to `<file>.1`, pushing older ones up to `<file>.<keep>`.
+/// Older files than that are deleted.
+#[derive(Clone, Copy, Debug)]
+pub struct Rotation {
+    /// The size in bytes a log file may grow to.
+    pub max_size: u64,
+    /// How many rotated files to keep.
+    pub keep: usize,
+}
+
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    rotation: Rotation,
+}
+
+impl LogFile {
+    fn open(path: &Path, rotation: Rotation) -> io::Result<LogFile> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(LogFile {
+            path: path.to_path_buf(),
+            size: file.metadata()?.len(),
+            file,
+            rotation,
+        })
+    }
+
+    fn write(&mut self, line: &str) -> io::Result<()> {
+        let length = line.len() as u64 + 1;
+        if self.size > 0 && self.size + length > self.rotation.max_size {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.size += length;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let rotated = |n: usize| {
+            let mut name = self.path.clone().into_os_string();
+            name.push(format!(".{}", n));
+            PathBuf::from(name)
+        };
+        if self.rotation.keep == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for n in (1..self.rotation.keep).rev() {
+                match fs::rename(rotated(n), rotated(n + 1)) {
+                    Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                    _ => {}
+                }
+            }
+            fs::rename(&self.path, rotated(1))?;
+        }
+        *self = LogFile::open(&self.path, self.rotation)?;
+        Ok(())
+    }
+}
+
+/// Writes the messages a `Filter` lets through to a file, or to stderr.
 pub struct Logger {
-    file: Option<Mutex<std::fs::File>>,
+    filter: Filter,
+    file: Option<Mutex<LogFile>>,
 }
 
 impl Logger {
-    pub fn new(file_path: Option<String>) -> Logger {
-        let file = file_path.map(|path| {
-            let file = OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(path)
-                .unwrap();
-            Mutex::new(file)
-        });
-        Logger { file }
-    }
-
-    pub fn log(&self, msg: String) {
+    /// A logger writing to stderr.
+    pub fn new(filter: Filter) -> Logger {
+        Logger { filter, file: None }
+    }
+
+    /// A logger appending to the file at `path`, creating it and its
+    /// directory if needed.
+    pub fn to_file(filter: Filter, path: &Path, rotation: Rotation) -> io::Result<Logger> {
+        let file = LogFile::open(path, rotation)?;
+        Ok(Logger {
+            filter,
+            file: Some(Mutex::new(file)),
+        })
+    }
+
+    /// Writes `message` from the module `target`, if the filter lets it
+    /// through.
+    pub fn log(&self, level: Level, target: &str, message: fmt::Arguments) {
+        if !self.filter.enabled(level, target) {
+            return;
+        }
+        let label = format!("{:5}", level.name().to_uppercase());
         match &self.file {
             Some(file) => {
+                let line = format!("{} {} {}: {}", timestamp(now()), label, target, message);
                 if let Ok(mut file) = file.lock() {
-                    writeln!(file, "{}", msg).unwrap();
+                    // There is nowhere left to report this to.
+                    let _ = file.write(&line);
                 }
             }
-            None => println!("{}", msg),
+            None => eprintln!(
+                "{} {} {}: {}",
+                style!(@stderr "dim", "{}", timestamp(now())),
+                style!(@stderr level.style(), "{}", label),
+                target,
+                message
+            ),
         }
     }
 }
+
+/// `i4.log` in the state directory.
+pub fn default_path() -> Option<PathBuf> {
+    crate::history::state_dir().map(|dir| dir.join("i4.log"))
+}
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// Installs the logger the macros write to. Until then, and if this is
+/// called again, they use a default one: warnings and errors to stderr.
+pub fn init(logger: Logger) {
+    let _ = LOGGER.set(logger);
+}
+
+/// What the logging macros call.
+pub fn log(level: Level, target: &str, message: fmt::Arguments) {
+    LOGGER
+        .get_or_init(|| Logger::new(Filter::default()))
+        .log(level, target, message);
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Formats milliseconds since the epoch as an RFC 3339 UTC timestamp.
+pub fn timestamp(millis: u64) -> String {
+    let seconds = millis / 1000;
+    let (hour, minute, second) = (seconds / 3600 % 24, seconds / 60 % 60, seconds % 60);
+
+    // Days since the epoch to a civil date, after Howard Hinnant's
+    // `civil_from_days`.
+    let days = (seconds / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+        millis % 1000
+    )
+}
@@ -1,7 +1,57 @@
-#[macro_export]
+//! Styling and logging macros.
+//!
+//! `style!` only emits colour codes when the text is going to a terminal and
+//! `NO_COLOR` isn't set. It assumes stdout, where results go; `style!(@stderr
+//! ...)` is for text meant for stderr.
+//!
+//! The macros are internal to the crate: they reach into `macros` and
+//! `logger`, which are not part of the library's API.
+
+// Not every colour and level is in use at any one time.
+#![allow(unused_macros)]
+
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::OnceLock;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+const DETECT: u8 = 0;
+const ON: u8 = 1;
+const OFF: u8 = 2;
+
+static STDOUT_COLOUR: AtomicU8 = AtomicU8::new(DETECT);
+
+/// Whether `style!` output for `stream` gets colour codes.
+pub fn colour(stream: Stream) -> bool {
+    static STDOUT: OnceLock<bool> = OnceLock::new();
+    static STDERR: OnceLock<bool> = OnceLock::new();
+    let no_colour = || std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    match stream {
+        Stream::Stdout => match STDOUT_COLOUR.load(Ordering::Relaxed) {
+            DETECT => *STDOUT.get_or_init(|| !no_colour() && std::io::stdout().is_terminal()),
+            setting => setting == ON,
+        },
+        Stream::Stderr => *STDERR.get_or_init(|| !no_colour() && std::io::stderr().is_terminal()),
+    }
+}
+
+/// Overrides the detection for stdout. The daemon uses this to style
+/// results for its client's terminal rather than its own.
+pub fn set_colour(on: bool) {
+    STDOUT_COLOUR.store(if on { ON } else { OFF }, Ordering::Relaxed);
+}
+
 macro_rules! style {
-    ($style:expr, $($arg:tt)*) => {
-        {
+    (@stderr $style:expr, $($arg:tt)*) => {
+        $crate::macros::style!(@to $crate::macros::Stream::Stderr, $style, $($arg)*)
+    };
+    (@to $stream:expr, $style:expr, $($arg:tt)*) => {
+        if $crate::macros::colour($stream) {
             let styles: Vec<&str> = $style.split(',').collect();
             let mut combined_styles = String::new();
 
@@ -28,156 +78,121 @@ macro_rules! style {
             }
 
             format!("{}{}\x1b[0m", combined_styles, format_args!($($arg)*))
+        } else {
+            format!("{}", format_args!($($arg)*))
         }
     };
+    ($style:expr, $($arg:tt)*) => {
+        $crate::macros::style!(@to $crate::macros::Stream::Stdout, $style, $($arg)*)
+    };
 }
 
 /** Colour macros */
-
-#[macro_export]
 macro_rules! black {
     ($($arg:tt)*) => {
         style!("black", "{}", format_args!($($arg)*))
     };
 }
 
-#[macro_export]
 macro_rules! red {
     ($($arg:tt)*) => {
         style!("red", "{}", format_args!($($arg)*))
     };
 }
 
-#[macro_export]
 macro_rules! green {
     ($($arg:tt)*) => {
         style!("green", "{}", format_args!($($arg)*))
     };
 }
 
-#[macro_export]
 macro_rules! yellow {
     ($($arg:tt)*) => {
         style!("yellow", "{}", format_args!($($arg)*))
     };
 }
 
-#[macro_export]
 macro_rules! blue {
     ($($arg:tt)*) => {
         style!("blue", "{}", format_args!($($arg)*))
     };
 }
 
-#[macro_export]
 macro_rules! magenta {
     ($($arg:tt)*) => {
         style!("magenta", "{}", format_args!($($arg)*))
     };
 }
 
-#[macro_export]
 macro_rules! cyan {
     ($($arg:tt)*) => {
         style!("cyan", "{}", format_args!($($arg)*))
     };
 }
 
-/** Logging macros */
-#[macro_export]
-macro_rules! trace {
-    ($($arg:tt)*) => {
+/** Logging macros, all going through `logger::log` */
+macro_rules! log {
+    ($level:expr, $($arg:tt)*) => {
         if cfg!(feature = "log") {
-            let log = std::env::var("RUST_LOG").unwrap_or(String::from("info"));
-            println!("log = {}", log);
-            if log.contains("trace") {
-                println!("{}{}", style!("dim,magenta", "[TRACE] "), style!("dim,white", "{}", format_args!($($arg)*)));
-            }
+            $crate::logger::log($level, module_path!(), format_args!($($arg)*));
         }
     };
 }
 
-#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        $crate::macros::log!($crate::logger::Level::Trace, $($arg)*)
+    };
+}
+
 macro_rules! debug {
     ($($arg:tt)*) => {
-        if cfg!(feature = "log") {
-            let log = std::env::var("RUST_LOG").unwrap_or(String::from("info"));
-            if log.contains("debug") || log.contains("trace") {
-                println!("{}{}", style!("magenta", "[DEBUG] "), style!("dim,white", "{}", format_args!($($arg)*)));
-            }
-        }
+        $crate::macros::log!($crate::logger::Level::Debug, $($arg)*)
     };
 }
 
-#[macro_export]
 macro_rules! info {
     ($($arg:tt)*) => {
-        if cfg!(feature = "log") {
-            let log = std::env::var("RUST_LOG").unwrap_or(String::from("info"));
-            if log.contains("info") || log.contains("debug") || log.contains("trace") {
-                println!("{}{}", style!("cyan", "[INFO] "), format_args!($($arg)*));
-            }
-        }
+        $crate::macros::log!($crate::logger::Level::Info, $($arg)*)
     };
 }
 
-#[macro_export]
 macro_rules! warning {
     ($($arg:tt)*) => {
-        if cfg!(feature = "log") {
-            let log = std::env::var("RUST_LOG").unwrap_or(String::from("info"));
-            if log.contains("warning") || log.contains("info") || log.contains("debug") || log.contains("trace") {
-                println!("{}{}", style!("yellow", "[WARN] "), format_args!($($arg)*));
-            }
-        }
+        $crate::macros::log!($crate::logger::Level::Warn, $($arg)*)
     };
 }
 
-#[macro_export]
 macro_rules! error {
     ($($arg:tt)*) => {
-        if cfg!(feature = "log") {
-            let log = std::env::var("RUST_LOG").unwrap_or(String::from("info"));
-            if log.contains("error") || log.contains("warning") || log.contains("info") || log.contains("debug") || log.contains("trace") {
-                println!("{}{}", style!("red", "[ERROR] "), format_args!($($arg)*));
-            }
-        }
+        $crate::macros::log!($crate::logger::Level::Error, $($arg)*)
     };
 }
 
-#[macro_export]
 macro_rules! failure {
     ($($arg:tt)*) => {
-        if cfg!(feature = "log") {
-            println!("{}{}", style!("red", "[FAILURE] "), format_args!($($arg)*));
-        }
+        $crate::macros::log!($crate::logger::Level::Error, "Failed: {}", format_args!($($arg)*))
     };
 }
 
-#[macro_export]
 macro_rules! success {
     ($($arg:tt)*) => {
-        if cfg!(feature = "log") {
-            println!("{}{}", style!("green", "[SUCCESS] "), format_args!($($arg)*));
-        }
+        $crate::macros::log!($crate::logger::Level::Info, "Done: {}", format_args!($($arg)*))
     };
 }
 
-#[macro_export]
 macro_rules! note {
     ($($arg:tt)*) => {
-        if cfg!(feature = "log") {
-            let log = std::env::var("RUST_LOG").unwrap_or(String::from("info"));
-            if log.contains("note") {
-                println!("{}{}", style!("dim,blue", "[NOTE] "), style!("dim,white", "{}", format_args!($($arg)*)));
-            }
-        }
+        $crate::macros::log!($crate::logger::Level::Debug, $($arg)*)
     };
 }
 
-#[macro_export]
 macro_rules! msg {
     ($($arg:tt)*) => {
         println!("{}", format_args!($($arg)*));
     };
 }
+
+// For the `$crate::macros::…` paths above. Elsewhere the macros are in
+// scope through `#[macro_use]`.
+pub(crate) use {log, style};
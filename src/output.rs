@@ -0,0 +1,184 @@
+//! The machine-readable output formats of `list` and `get`.
+
+use crate::direction::Rect;
+use crate::Node;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// How `list` and `get` print their results.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Human-readable, coloured on a terminal
+    Text,
+    /// One JSON document
+    Json,
+    /// Tab-separated columns, one node per row
+    Tsv,
+}
+
+/// A rect as an object with named fields.
+#[derive(Serialize)]
+pub struct RectRecord {
+    /// Left edge, in absolute display coordinates.
+    pub x: i32,
+    /// Top edge, in absolute display coordinates.
+    pub y: i32,
+    /// Width in pixels.
+    pub width: i32,
+    /// Height in pixels.
+    pub height: i32,
+}
+
+impl From<Rect> for RectRecord {
+    fn from((x, y, width, height): Rect) -> Self {
+        RectRecord {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+/// The machine-readable view of a `Node`. Field names are part of i4's
+/// interface, so rename with care.
+#[derive(Serialize)]
+pub struct NodeRecord {
+    /// `NodeType::name`, under the key `type`.
+    #[serde(rename = "type")]
+    pub node_type: &'static str,
+    /// The container id.
+    pub id: i64,
+    /// The window title, or the output or workspace name.
+    pub name: Option<String>,
+    /// Where the node is on screen.
+    pub rect: RectRecord,
+    /// Whether the node is the focused one.
+    pub focused: bool,
+    /// The name of the workspace the node is on.
+    pub workspace: Option<String>,
+    /// The name of the output the node is on.
+    pub output: Option<String>,
+    /// sway only: the Wayland app id of a native Wayland window.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_id: Option<String>,
+    /// sway only: the process id of the window's client.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pid: Option<i32>,
+    /// The children, for `list all`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nodes: Option<Vec<NodeRecord>>,
+    /// The floating children, for `list all`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub floating_nodes: Option<Vec<NodeRecord>>,
+}
+
+impl NodeRecord {
+    /// A single node without its children.
+    pub fn new(node: &Node) -> Self {
+        NodeRecord {
+            node_type: node.get_node_type().name(),
+            id: node.current.id,
+            name: node.current.name.clone(),
+            rect: node.current.rect.into(),
+            focused: node.current.focused,
+            workspace: node
+                .get_parent_workspace()
+                .and_then(|workspace| workspace.current.name.clone()),
+            output: node
+                .get_parent_output()
+                .and_then(|output| output.current.name.clone()),
+            app_id: node.current.app_id.clone(),
+            pid: node.current.pid,
+            nodes: None,
+            floating_nodes: None,
+        }
+    }
+
+    /// A node with all of its descendants nested under `nodes` and
+    /// `floating_nodes`.
+    pub fn tree(node: &Node) -> Self {
+        let mut record = NodeRecord::new(node);
+        record.nodes = Some(node.children().iter().map(NodeRecord::tree).collect());
+        record.floating_nodes = Some(
+            node.floating_children()
+                .iter()
+                .map(NodeRecord::tree)
+                .collect(),
+        );
+        record
+    }
+
+    /// One tab-separated row per node, descendants following their parent and
+    /// floating ones after the rest.
+    pub fn to_tsv(&self) -> Vec<String> {
+        fn field(value: &Option<String>) -> String {
+            value
+                .as_deref()
+                .unwrap_or("")
+                .replace(['\t', '\n', '\r'], " ")
+        }
+
+        let mut rows = vec![[
+            self.node_type.to_string(),
+            self.id.to_string(),
+            field(&self.name),
+            self.rect.x.to_string(),
+            self.rect.y.to_string(),
+            self.rect.width.to_string(),
+            self.rect.height.to_string(),
+            self.focused.to_string(),
+            field(&self.workspace),
+            field(&self.output),
+        ]
+        .join("\t")];
+        for child in self.nodes.iter().chain(&self.floating_nodes).flatten() {
+            rows.extend(child.to_tsv());
+        }
+        rows
+    }
+}
+
+/// The machine-readable view of `list focused`.
+#[derive(Serialize)]
+pub struct FocusedRecord {
+    /// The focused node.
+    pub focused: NodeRecord,
+    /// The window before it in the tree.
+    pub previous: Option<NodeRecord>,
+    /// The window after it in the tree.
+    pub next: Option<NodeRecord>,
+}
+
+impl FocusedRecord {
+    /// The records of `node` and its neighbours in tree order.
+    pub fn new(node: &Node) -> Self {
+        FocusedRecord {
+            focused: NodeRecord::new(node),
+            previous: node.previous_window().as_ref().map(NodeRecord::new),
+            next: node.next_window().as_ref().map(NodeRecord::new),
+        }
+    }
+}
+
+/// Renders a single record as a JSON object or as TSV rows. Text output is
+/// left to the callers, which know how each node should read.
+pub fn render_node(format: OutputFormat, record: &NodeRecord) -> String {
+    match format {
+        OutputFormat::Json => serde_json::to_string(record).unwrap(),
+        OutputFormat::Tsv | OutputFormat::Text => record.to_tsv().join("\n"),
+    }
+}
+
+/// Renders a list of records as a JSON array or as TSV rows.
+pub fn render_nodes(format: OutputFormat, records: &[NodeRecord]) -> String {
+    match format {
+        OutputFormat::Json => serde_json::to_string(records).unwrap(),
+        OutputFormat::Tsv | OutputFormat::Text => records
+            .iter()
+            .flat_map(NodeRecord::to_tsv)
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
@@ -0,0 +1,365 @@
+//! `list --filter`: a small expression language for picking windows.
+//!
+//! ```text
+//! class=Firefox && workspace=2
+//! title~'^vim ' || marks=scratch
+//! !floating && (urgent || output=HDMI-1)
+//! ```
+//!
+//! `=` and `!=` compare exactly, `~` and `!~` match a regex anywhere in the
+//! value. The flags `floating`, `urgent` and `focused` stand on their own or
+//! compare to `true` or `false`. `&&` binds tighter than `||`. Values with
+//! spaces or operator characters go in single or double quotes.
+
+use crate::Node;
+use regex::Regex;
+use std::fmt;
+
+/// A parsed `--filter` expression.
+#[derive(Clone, Debug)]
+pub struct Query(Expr);
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Flag(Flag, bool),
+    Compare(Field, Test),
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Flag {
+    Floating,
+    Urgent,
+    Focused,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Field {
+    Class,
+    Instance,
+    Title,
+    Workspace,
+    Output,
+    Marks,
+}
+
+#[derive(Clone, Debug)]
+enum Test {
+    Equals(String),
+    Matches(Regex),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Not,
+    And,
+    Or,
+    Equals,
+    NotEquals,
+    Matches,
+    NotMatches,
+    /// A bare or quoted word.
+    Word(String),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Open => write!(f, "`(`"),
+            Token::Close => write!(f, "`)`"),
+            Token::Not => write!(f, "`!`"),
+            Token::And => write!(f, "`&&`"),
+            Token::Or => write!(f, "`||`"),
+            Token::Equals => write!(f, "`=`"),
+            Token::NotEquals => write!(f, "`!=`"),
+            Token::Matches => write!(f, "`~`"),
+            Token::NotMatches => write!(f, "`!~`"),
+            Token::Word(word) => write!(f, "`{}`", word),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '=' => Token::Equals,
+            '~' => Token::Matches,
+            '!' => match chars.peek() {
+                Some('=') => {
+                    chars.next();
+                    Token::NotEquals
+                }
+                Some('~') => {
+                    chars.next();
+                    Token::NotMatches
+                }
+                _ => Token::Not,
+            },
+            '&' | '|' => {
+                if chars.next() != Some(c) {
+                    return Err(format!("Expected `{}{}`", c, c));
+                }
+                if c == '&' {
+                    Token::And
+                } else {
+                    Token::Or
+                }
+            }
+            '\'' | '"' => {
+                let mut word = String::new();
+                loop {
+                    match chars.next() {
+                        Some(end) if end == c => break,
+                        Some(other) => word.push(other),
+                        None => return Err(format!("Unclosed {} quote", c)),
+                    }
+                }
+                Token::Word(word)
+            }
+            c => {
+                let mut word = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || "()=~!&|'\"".contains(next) {
+                        break;
+                    }
+                    word.push(next);
+                    chars.next();
+                }
+                Token::Word(word)
+            }
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// A recursive descent parser over the tokens, one method per precedence
+/// level.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.unary()?))),
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    Some(token) => Err(format!("Expected `)`, found {}", token)),
+                    None => Err("Expected `)`".to_string()),
+                }
+            }
+            Some(Token::Word(name)) => self.comparison(&name),
+            Some(token) => Err(format!("Expected a field, found {}", token)),
+            None => Err("Expected a field".to_string()),
+        }
+    }
+
+    fn comparison(&mut self, name: &str) -> Result<Expr, String> {
+        let operator = match self.peek() {
+            Some(
+                token @ (Token::Equals | Token::NotEquals | Token::Matches | Token::NotMatches),
+            ) => Some(token.clone()),
+            _ => None,
+        };
+        if let Some(flag) = flag(name) {
+            return match operator {
+                None => Ok(Expr::Flag(flag, true)),
+                Some(Token::Equals | Token::NotEquals) => {
+                    self.next();
+                    let value = match self.value(name)?.as_str() {
+                        "true" => true,
+                        "false" => false,
+                        other => {
+                            return Err(format!("`{}` is true or false, not `{}`", name, other))
+                        }
+                    };
+                    Ok(Expr::Flag(flag, value == (operator == Some(Token::Equals))))
+                }
+                Some(token) => Err(format!("`{}` can't be used with {}", name, token)),
+            };
+        }
+
+        let field = self::field(name)?;
+        let operator = operator.ok_or_else(|| format!("`{}` needs a value", name))?;
+        self.next();
+        let value = self.value(name)?;
+        let test = match operator {
+            Token::Equals | Token::NotEquals => Test::Equals(value),
+            _ => Test::Matches(
+                Regex::new(&value).map_err(|err| format!("Invalid regex `{}`: {}", value, err))?,
+            ),
+        };
+        let expr = Expr::Compare(field, test);
+        match operator {
+            Token::NotEquals | Token::NotMatches => Ok(Expr::Not(Box::new(expr))),
+            _ => Ok(expr),
+        }
+    }
+
+    fn value(&mut self, name: &str) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Word(value)) => Ok(value),
+            _ => Err(format!("`{}` needs a value", name)),
+        }
+    }
+}
+
+fn flag(name: &str) -> Option<Flag> {
+    match name {
+        "floating" => Some(Flag::Floating),
+        "urgent" => Some(Flag::Urgent),
+        "focused" => Some(Flag::Focused),
+        _ => None,
+    }
+}
+
+fn field(name: &str) -> Result<Field, String> {
+    match name {
+        "class" => Ok(Field::Class),
+        "instance" => Ok(Field::Instance),
+        "title" => Ok(Field::Title),
+        "workspace" => Ok(Field::Workspace),
+        "output" => Ok(Field::Output),
+        "marks" => Ok(Field::Marks),
+        _ => Err(format!(
+            "Unknown field `{}`, expected one of: class, instance, title, workspace, \
+             output, marks, floating, urgent, focused",
+            name
+        )),
+    }
+}
+
+impl Query {
+    /// Parses an expression. Also serves as the clap value parser of
+    /// `--filter`, so a broken one is a usage error.
+    pub fn parse(input: &str) -> Result<Query, String> {
+        let mut parser = Parser {
+            tokens: tokenize(input)?,
+            position: 0,
+        };
+        let expr = parser.or()?;
+        match parser.next() {
+            None => Ok(Query(expr)),
+            Some(token) => Err(format!("Unexpected {}", token)),
+        }
+    }
+
+    /// Whether `window` satisfies the expression.
+    pub fn matches(&self, window: &Node) -> bool {
+        self.0.matches(window)
+    }
+}
+
+impl Expr {
+    fn matches(&self, window: &Node) -> bool {
+        match self {
+            Expr::Not(expr) => !expr.matches(window),
+            Expr::And(left, right) => left.matches(window) && right.matches(window),
+            Expr::Or(left, right) => left.matches(window) || right.matches(window),
+            Expr::Flag(flag, value) => {
+                let con = &window.current;
+                let set = match flag {
+                    Flag::Floating => window.is_floating(),
+                    Flag::Urgent => con.urgent,
+                    Flag::Focused => con.focused,
+                };
+                set == *value
+            }
+            Expr::Compare(field, test) => values(*field, window)
+                .iter()
+                .any(|value| test.matches(value)),
+        }
+    }
+}
+
+impl Test {
+    fn matches(&self, value: &str) -> bool {
+        match self {
+            Test::Equals(expected) => value == expected,
+            Test::Matches(regex) => regex.is_match(value),
+        }
+    }
+}
+
+/// What a field holds for a window: none, one or, for `marks`, several
+/// values, of which any may match.
+fn values(field: Field, window: &Node) -> Vec<String> {
+    let con = &window.current;
+    let properties = con.window_properties.clone().unwrap_or_default();
+    match field {
+        // Wayland views on sway have an app id instead of a class.
+        Field::Class => properties
+            .class
+            .or_else(|| con.app_id.clone())
+            .into_iter()
+            .collect(),
+        Field::Instance => properties.instance.into_iter().collect(),
+        Field::Title => properties
+            .title
+            .or_else(|| con.name.clone())
+            .into_iter()
+            .collect(),
+        // A workspace goes by its name, and by its number if it has one.
+        Field::Workspace => match window
+            .get_parent_workspace()
+            .and_then(|w| w.current.name.clone())
+        {
+            Some(name) => {
+                let number: String = name.chars().take_while(char::is_ascii_digit).collect();
+                if number.is_empty() || number == name {
+                    vec![name]
+                } else {
+                    vec![name, number]
+                }
+            }
+            None => Vec::new(),
+        },
+        Field::Output => window
+            .get_parent_output()
+            .and_then(|output| output.current.name.clone())
+            .into_iter()
+            .collect(),
+        Field::Marks => con.marks.clone(),
+    }
+}
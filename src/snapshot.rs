@@ -0,0 +1,143 @@
+//! `i4 snapshot save`: the layout as a file that `--tree-file` can load, so
+//! that a navigation bug can be reproduced away from the machine it
+//! happened on. It holds i3's replies as they came, fields i4 has no use for
+//! included.
+//!
+//! With `--anonymize` only the fields i4 reads are kept, and the window
+//! titles, classes, instances and app ids, and the workspace names, are
+//! swapped for pseudonyms like `title-3`. The same string always gets the
+//! same pseudonym, so windows of one application still share a class.
+//! Everything navigation depends on stays: the structure, ids, rects,
+//! layouts, marks, focus and workspace numbers.
+
+use crate::backend::{self, Backend};
+use crate::error::Error;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Writes the snapshot to `file`, or returns it when `file` is `-`.
+pub fn save(backend: &mut dyn Backend, file: &Path, anonymize: bool) -> Result<String, Error> {
+    // Fields i4 doesn't know could hold anything, so an anonymized snapshot
+    // starts from the parsed replies.
+    let snapshot = if anonymize {
+        let mut snapshot = backend::snapshot(backend)?;
+        self::anonymize(&mut snapshot);
+        snapshot
+    } else {
+        backend.get_snapshot()?
+    };
+    let json = serde_json::to_string_pretty(&snapshot).unwrap();
+    if file == Path::new("-") {
+        return Ok(json);
+    }
+    std::fs::write(file, json + "\n")
+        .map_err(|err| Error::Snapshot(format!("{}: {}", file.display(), err)))?;
+    info!("Saved the layout to {}", file.display());
+    Ok(String::new())
+}
+
+/// Hands out `<kind>-1`, `<kind>-2`, … in order of first appearance.
+struct Pseudonyms {
+    kind: &'static str,
+    names: HashMap<String, String>,
+}
+
+impl Pseudonyms {
+    fn new(kind: &'static str) -> Self {
+        Pseudonyms {
+            kind,
+            names: HashMap::new(),
+        }
+    }
+
+    fn get(&mut self, name: &str) -> String {
+        let next = self.names.len() + 1;
+        self.names
+            .entry(name.to_string())
+            .or_insert_with(|| format!("{}-{}", self.kind, next))
+            .clone()
+    }
+
+    /// Replaces the string at `key` of `value`, if there is one.
+    fn replace(&mut self, value: &mut Value, key: &str) {
+        if let Some(name) = value.get(key).and_then(Value::as_str) {
+            value[key] = Value::String(self.get(name));
+        }
+    }
+}
+
+struct Anonymizer {
+    titles: Pseudonyms,
+    classes: Pseudonyms,
+    instances: Pseudonyms,
+    app_ids: Pseudonyms,
+    workspaces: Pseudonyms,
+}
+
+impl Anonymizer {
+    fn node(&mut self, node: &mut Value) {
+        let is_window = ["window", "app_id", "shell"]
+            .iter()
+            .any(|key| node.get(key).is_some_and(|value| !value.is_null()));
+        if is_window {
+            // A window's name is its title.
+            self.titles.replace(node, "name");
+            self.app_ids.replace(node, "app_id");
+            if let Some(properties) = node.get_mut("window_properties") {
+                self.titles.replace(properties, "title");
+                self.classes.replace(properties, "class");
+                self.instances.replace(properties, "instance");
+            }
+        } else if node.get("type").and_then(Value::as_str) == Some("workspace") {
+            self.workspace(node, "name");
+        }
+        for key in ["nodes", "floating_nodes"] {
+            if let Some(children) = node.get_mut(key).and_then(Value::as_array_mut) {
+                children.iter_mut().for_each(|child| self.node(child));
+            }
+        }
+    }
+
+    /// Keeps the number a workspace name starts with, which the grid goes
+    /// by, and i3's own scratchpad workspace.
+    fn workspace(&mut self, value: &mut Value, key: &str) {
+        let name = match value.get(key).and_then(Value::as_str) {
+            Some(name) if !name.starts_with("__i3") => name.to_string(),
+            _ => return,
+        };
+        let number: String = name.chars().take_while(char::is_ascii_digit).collect();
+        if number == name {
+            return;
+        }
+        let pseudonym = self.workspaces.get(&name);
+        value[key] = Value::String(if number.is_empty() {
+            pseudonym
+        } else {
+            format!("{}: {}", number, pseudonym)
+        });
+    }
+}
+
+/// Replaces everything in a snapshot that could say what the user was
+/// doing, keeping the `workspaces` and `outputs` replies in line with the
+/// tree.
+pub fn anonymize(snapshot: &mut Value) {
+    let mut anonymizer = Anonymizer {
+        titles: Pseudonyms::new("title"),
+        classes: Pseudonyms::new("class"),
+        instances: Pseudonyms::new("instance"),
+        app_ids: Pseudonyms::new("app"),
+        workspaces: Pseudonyms::new("workspace"),
+    };
+    if let Some(tree) = snapshot.get_mut("tree") {
+        anonymizer.node(tree);
+    }
+    for (reply, key) in [("workspaces", "name"), ("outputs", "current_workspace")] {
+        if let Some(entries) = snapshot.get_mut(reply).and_then(Value::as_array_mut) {
+            for entry in entries {
+                anonymizer.workspace(entry, key);
+            }
+        }
+    }
+}
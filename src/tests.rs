@@ -0,0 +1,1298 @@
+use crate::backend::{self, Backend, CachedBackend, FixtureBackend, IpcBackend};
+use crate::cli::{Cli, Command, ListTarget, Navigation, Target, WorkspaceAction};
+use crate::config::Config;
+use crate::daemon::State;
+use crate::direction::{Direction, Scope, Wrap};
+use crate::grid::Grid;
+use crate::history::History;
+use crate::logger::{self, Filter, Level, Logger, Rotation};
+use crate::output::OutputFormat;
+use crate::query::Query;
+use crate::tree::Con;
+use crate::{daemon, execute, execute_cli, macros, render_nodes, snapshot, Node, NodeType};
+use std::ffi::OsString;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixListener;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+fn fixture_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(format!("{}.json", name))
+}
+
+fn fixture(name: &str) -> FixtureBackend {
+    FixtureBackend::load(&fixture_path(name)).unwrap()
+}
+
+fn tree(backend: &mut FixtureBackend) -> Con {
+    backend.get_tree().unwrap()
+}
+
+fn tree_of(name: &str) -> Con {
+    tree(&mut fixture(name))
+}
+
+fn window<'a>(root: &Node<'a>, name: &str) -> Node<'a> {
+    root.get_windows()
+        .into_iter()
+        .find(|window| window.current.name.as_deref() == Some(name))
+        .unwrap_or_else(|| panic!("No window named {:?}", name))
+}
+
+fn navigation(target: Target) -> Navigation {
+    Navigation {
+        target,
+        n: None,
+        scope: Scope::Workspace,
+        wrap: Wrap::None,
+        timeout: 1000,
+    }
+}
+
+fn name(node: Option<Node>) -> Option<String> {
+    node.and_then(|node| node.current.name.clone())
+}
+
+#[test]
+fn get_focused_finds_the_focused_window() {
+    let con = tree_of("three_columns");
+    let root = Node::root(&con);
+    assert_eq!(name(root.get_focused()).as_deref(), Some("Mozilla Firefox"));
+
+    let con = tree_of("nested_splits");
+    let root = Node::root(&con);
+    assert_eq!(name(root.get_focused()).as_deref(), Some("htop"));
+}
+
+#[test]
+fn next_and_previous_follow_tree_order() {
+    let con = tree_of("three_columns");
+    let root = Node::root(&con);
+    let terminal = window(&root, "Terminal");
+    let firefox = window(&root, "Mozilla Firefox");
+    let code = window(&root, "notes.md - Code");
+
+    assert_eq!(
+        name(terminal.next_window()).as_deref(),
+        Some("Mozilla Firefox")
+    );
+    assert_eq!(
+        name(firefox.next_window()).as_deref(),
+        Some("notes.md - Code")
+    );
+    assert_eq!(
+        name(code.previous_window()).as_deref(),
+        Some("Mozilla Firefox")
+    );
+    assert_eq!(name(firefox.previous_window()).as_deref(), Some("Terminal"));
+    assert_eq!(name(terminal.previous_window()), None);
+    assert_eq!(name(code.next_window()), None);
+}
+
+#[test]
+fn next_and_previous_descend_into_split_containers() {
+    let con = tree_of("nested_splits");
+    let root = Node::root(&con);
+    let terminal = window(&root, "Terminal");
+    let calculator = window(&root, "Calculator");
+
+    assert_eq!(
+        name(terminal.next_window()).as_deref(),
+        Some("Mozilla Firefox")
+    );
+    assert_eq!(name(calculator.previous_window()).as_deref(), Some("htop"));
+    assert_eq!(
+        name(window(&root, "Mozilla Firefox").next_window()).as_deref(),
+        Some("htop")
+    );
+}
+
+#[test]
+fn neighbours_in_a_single_row() {
+    let con = tree_of("three_columns");
+    let root = Node::root(&con);
+    let firefox = window(&root, "Mozilla Firefox");
+
+    assert_eq!(
+        name(firefox.get_neighbour(Direction::Left)).as_deref(),
+        Some("Terminal")
+    );
+    assert_eq!(
+        name(firefox.get_neighbour(Direction::Right)).as_deref(),
+        Some("notes.md - Code")
+    );
+    assert_eq!(name(firefox.get_neighbour(Direction::Up)), None);
+    assert_eq!(name(firefox.get_neighbour(Direction::Down)), None);
+}
+
+#[test]
+fn neighbours_cross_split_boundaries() {
+    let con = tree_of("nested_splits");
+    let root = Node::root(&con);
+    let htop = window(&root, "htop");
+
+    assert_eq!(
+        name(htop.get_neighbour(Direction::Left)).as_deref(),
+        Some("Terminal")
+    );
+    assert_eq!(
+        name(htop.get_neighbour(Direction::Up)).as_deref(),
+        Some("Mozilla Firefox")
+    );
+    assert_eq!(
+        name(htop.get_neighbour(Direction::Right)).as_deref(),
+        Some("Calculator")
+    );
+    assert_eq!(name(htop.get_neighbour(Direction::Down)), None);
+
+    let calculator = window(&root, "Calculator");
+    assert_eq!(
+        name(calculator.get_neighbour(Direction::Left)).as_deref(),
+        Some("htop")
+    );
+    assert_eq!(
+        name(calculator.get_neighbour(Direction::Up)).as_deref(),
+        Some("Mozilla Firefox")
+    );
+}
+
+#[test]
+fn neighbours_skip_hidden_tabs() {
+    let con = tree_of("tabbed");
+    let root = Node::root(&con);
+    let terminal = window(&root, "Terminal");
+
+    assert_eq!(
+        name(terminal.get_neighbour(Direction::Left)).as_deref(),
+        Some("Inbox - Thunderbird")
+    );
+    assert_eq!(
+        name(window(&root, "Chat - Slack").get_neighbour(Direction::Right)).as_deref(),
+        Some("Terminal")
+    );
+}
+
+#[test]
+fn neighbours_continue_onto_the_adjacent_output() {
+    let con = tree_of("two_outputs");
+    let root = Node::root(&con);
+    let firefox = window(&root, "Mozilla Firefox");
+    let code = window(&root, "notes.md - Code");
+    assert_eq!(
+        name(firefox.get_neighbour(Direction::Right)).as_deref(),
+        Some("notes.md - Code")
+    );
+    assert_eq!(
+        name(code.get_neighbour(Direction::Left)).as_deref(),
+        Some("Mozilla Firefox")
+    );
+    assert_eq!(name(code.get_neighbour(Direction::Right)), None);
+    assert_eq!(
+        name(window(&root, "Terminal").get_neighbour(Direction::Left)),
+        None
+    );
+}
+
+#[test]
+fn neighbours_across_offset_outputs() {
+    let con = tree_of("offset_outputs");
+    let root = Node::root(&con);
+    assert_eq!(
+        name(window(&root, "htop").get_neighbour(Direction::Right)).as_deref(),
+        Some("notes.md - Code")
+    );
+    // Firefox faces no window on the lower monitor; the closest one wins.
+    assert_eq!(
+        name(window(&root, "Mozilla Firefox").get_neighbour(Direction::Right)).as_deref(),
+        Some("notes.md - Code")
+    );
+    assert_eq!(
+        name(window(&root, "notes.md - Code").get_neighbour(Direction::Left)).as_deref(),
+        Some("htop")
+    );
+    assert_eq!(
+        name(window(&root, "Spotify").get_neighbour(Direction::Left)).as_deref(),
+        Some("htop")
+    );
+}
+
+#[test]
+fn next_and_previous_continue_past_empty_docks() {
+    let con = tree_of("two_outputs");
+    let root = Node::root(&con);
+    assert_eq!(
+        name(window(&root, "Mozilla Firefox").next_window()).as_deref(),
+        Some("notes.md - Code")
+    );
+    assert_eq!(
+        name(window(&root, "notes.md - Code").previous_window()).as_deref(),
+        Some("Mozilla Firefox")
+    );
+}
+
+#[test]
+fn next_and_previous_wrap_within_the_region() {
+    let con = tree_of("two_outputs");
+    let root = Node::root(&con);
+    let firefox = window(&root, "Mozilla Firefox");
+    let terminal = window(&root, "Terminal");
+    let spotify = window(&root, "Spotify");
+
+    assert_eq!(
+        name(firefox.next_window_in(Scope::All, Wrap::None)).as_deref(),
+        Some("notes.md - Code")
+    );
+    assert_eq!(
+        name(firefox.next_window_in(Scope::All, Wrap::Workspace)).as_deref(),
+        Some("Terminal")
+    );
+    assert_eq!(
+        name(terminal.previous_window_in(Scope::All, Wrap::Output)).as_deref(),
+        Some("Mozilla Firefox")
+    );
+    // Spotify's workspace isn't showing, so the walk goes round past it.
+    assert_eq!(
+        name(terminal.previous_window_in(Scope::All, Wrap::Global)).as_deref(),
+        Some("notes.md - Code")
+    );
+    assert_eq!(
+        name(spotify.next_window_in(Scope::All, Wrap::Global)).as_deref(),
+        Some("Terminal")
+    );
+    assert_eq!(name(spotify.next_window_in(Scope::All, Wrap::None)), None);
+}
+
+#[test]
+fn next_and_previous_stay_within_the_scope() {
+    let con = tree_of("two_outputs");
+    let root = Node::root(&con);
+    let firefox = window(&root, "Mozilla Firefox");
+    let code = window(&root, "notes.md - Code");
+
+    assert_eq!(
+        name(firefox.next_window_in(Scope::Workspace, Wrap::None)),
+        None
+    );
+    assert_eq!(
+        name(firefox.next_window_in(Scope::Output, Wrap::None)),
+        None
+    );
+    assert_eq!(
+        name(firefox.next_window_in(Scope::All, Wrap::None)).as_deref(),
+        Some("notes.md - Code")
+    );
+    // Spotify is on the same output, on a workspace it isn't showing.
+    assert_eq!(name(code.next_window_in(Scope::Output, Wrap::None)), None);
+    assert_eq!(
+        name(code.next_window_in(Scope::Workspace, Wrap::None)),
+        None
+    );
+    assert_eq!(
+        name(code.previous_window_in(Scope::Workspace, Wrap::None)),
+        None
+    );
+
+    let con = tree_of("nested_splits");
+    let root = Node::root(&con);
+    let htop = window(&root, "htop");
+    assert_eq!(
+        name(htop.previous_window_in(Scope::Container, Wrap::None)),
+        None
+    );
+    assert_eq!(
+        name(htop.previous_window_in(Scope::Workspace, Wrap::None)).as_deref(),
+        Some("Mozilla Firefox")
+    );
+    // An explicit wrap region takes over from the scope.
+    assert_eq!(
+        name(window(&root, "Calculator").next_window_in(Scope::Container, Wrap::Workspace))
+            .as_deref(),
+        Some("Terminal")
+    );
+}
+
+#[test]
+fn neighbours_wrap_around_the_region() {
+    let con = tree_of("three_columns");
+    let root = Node::root(&con);
+    let code = window(&root, "notes.md - Code");
+    assert_eq!(
+        name(code.get_neighbour_wrapping(Direction::Right, Wrap::Workspace)).as_deref(),
+        Some("Terminal")
+    );
+    assert_eq!(
+        name(window(&root, "Terminal").get_neighbour_wrapping(Direction::Left, Wrap::Workspace))
+            .as_deref(),
+        Some("notes.md - Code")
+    );
+    assert_eq!(
+        name(code.get_neighbour_wrapping(Direction::Right, Wrap::None)),
+        None
+    );
+    // The only window in its column has nothing to wrap around to.
+    assert_eq!(
+        name(code.get_neighbour_wrapping(Direction::Up, Wrap::Workspace)),
+        None
+    );
+
+    let con = tree_of("two_outputs");
+    let root = Node::root(&con);
+    let firefox = window(&root, "Mozilla Firefox");
+    assert_eq!(
+        name(firefox.get_neighbour_wrapping(Direction::Right, Wrap::Output)).as_deref(),
+        Some("Terminal")
+    );
+    assert_eq!(
+        name(firefox.get_neighbour_wrapping(Direction::Right, Wrap::Global)).as_deref(),
+        Some("notes.md - Code")
+    );
+    assert_eq!(
+        name(
+            window(&root, "notes.md - Code").get_neighbour_wrapping(Direction::Right, Wrap::Global)
+        )
+        .as_deref(),
+        Some("Terminal")
+    );
+}
+
+#[test]
+fn visible_windows_skip_hidden_workspaces_and_tabs() {
+    let mut backend = fixture("two_outputs");
+    let con = tree(&mut backend);
+    let root = Node::root(&con);
+    let workspaces = backend.get_workspaces().unwrap();
+    let outputs = backend.get_outputs().unwrap();
+    let visible: Vec<String> = root
+        .get_visible_windows(&workspaces, &outputs)
+        .into_iter()
+        .filter_map(|window| window.current.name.clone())
+        .collect();
+    assert_eq!(visible, ["Terminal", "Mozilla Firefox", "notes.md - Code"]);
+
+    let mut backend = fixture("tabbed");
+    let con = tree(&mut backend);
+    let root = Node::root(&con);
+    let workspaces = backend.get_workspaces().unwrap();
+    let outputs = backend.get_outputs().unwrap();
+    let visible: Vec<String> = root
+        .get_visible_windows(&workspaces, &outputs)
+        .into_iter()
+        .filter_map(|window| window.current.name.clone())
+        .collect();
+    assert_eq!(visible, ["Inbox - Thunderbird", "Terminal"]);
+
+    // Floating windows are drawn over the tabs, whichever one is showing.
+    let mut backend = fixture("floating_tabbed");
+    let con = tree(&mut backend);
+    let root = Node::root(&con);
+    let workspaces = backend.get_workspaces().unwrap();
+    let outputs = backend.get_outputs().unwrap();
+    let visible: Vec<String> = root
+        .get_visible_windows(&workspaces, &outputs)
+        .into_iter()
+        .filter_map(|window| window.current.name.clone())
+        .collect();
+    assert_eq!(visible, ["Mozilla Firefox", "Calculator"]);
+}
+
+#[test]
+fn focus_and_move_send_commands_for_the_target() {
+    let mut backend = fixture("nested_splits");
+    let con = tree(&mut backend);
+    let root = Node::root(&con);
+    let htop = window(&root, "htop").current.id;
+    let firefox = window(&root, "Mozilla Firefox").current.id;
+
+    execute(
+        Command::Focus {
+            navigation: navigation(Target::Up),
+        },
+        OutputFormat::Text,
+        &mut backend,
+        &mut History::in_memory(),
+        &Config::default(),
+    )
+    .unwrap();
+    execute(
+        Command::Move {
+            navigation: navigation(Target::Up),
+        },
+        OutputFormat::Text,
+        &mut backend,
+        &mut History::in_memory(),
+        &Config::default(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        backend.commands,
+        [
+            format!("[con_id={}] focus", firefox),
+            format!("[con_id={}] swap container with con_id {}", htop, firefox),
+        ]
+    );
+}
+
+#[test]
+fn navigation_starts_from_a_focused_floating_window() {
+    let mut backend = fixture("floating");
+    let con = tree(&mut backend);
+    let root = Node::root(&con);
+    assert_eq!(name(root.get_focused()).as_deref(), Some("Calculator"));
+    let firefox = window(&root, "Mozilla Firefox").current.id;
+
+    let focused = execute(
+        Command::List {
+            target: ListTarget::Focused,
+            filter: None,
+        },
+        OutputFormat::Json,
+        &mut backend,
+        &mut History::in_memory(),
+        &Config::default(),
+    )
+    .unwrap();
+    let focused: serde_json::Value = serde_json::from_str(&focused).unwrap();
+    assert_eq!(focused["focused"]["name"], "Calculator");
+
+    execute(
+        Command::Focus {
+            navigation: navigation(Target::Right),
+        },
+        OutputFormat::Text,
+        &mut backend,
+        &mut History::in_memory(),
+        &Config::default(),
+    )
+    .unwrap();
+    assert_eq!(backend.commands, [format!("[con_id={}] focus", firefox)]);
+}
+
+/// What `i4 <args>` prints for the `name` fixture.
+fn output_of(name: &str, args: &[&str]) -> String {
+    let cli =
+        Cli::try_parse_from_with(std::iter::once(&"i4").chain(args), &Config::default()).unwrap();
+    execute(
+        cli.command,
+        cli.format,
+        &mut fixture(name),
+        &mut History::in_memory(),
+        &Config::default(),
+    )
+    .unwrap()
+}
+
+#[test]
+fn machine_output_keeps_its_keys_and_columns() {
+    let terminal = r#"{"type":"window","id":94000000000000,"name":"Terminal","rect":{"x":0,"y":0,"width":640,"height":1080},"focused":false,"workspace":"1","output":"eDP-1"}"#;
+    let json = |args: &[&str]| output_of("three_columns", &[&["-f", "json"], args].concat());
+    assert_eq!(json(&["get", "left"]), terminal);
+    let windows = json(&["list", "windows"]);
+    assert!(
+        windows.starts_with(&format!("[{},{{", terminal)),
+        "{}",
+        windows
+    );
+    let focused = json(&["list", "focused"]);
+    assert!(
+        focused.starts_with(r#"{"focused":{"type":"window","id":94000000004096,"#),
+        "{}",
+        focused
+    );
+    assert!(
+        focused.contains(&format!(r#","previous":{},"next":{{"#, terminal)),
+        "{}",
+        focused
+    );
+    let all = json(&["list", "all"]);
+    assert!(
+        all.starts_with(r#"{"type":"root","id":94000000045056,"name":"root","rect":{"x":0,"y":0,"width":1920,"height":1080},"focused":false,"workspace":null,"output":null,"nodes":[{"type":"output","#),
+        "{}",
+        all
+    );
+    assert!(all.ends_with(r#"]}],"floating_nodes":[]}"#), "{}", all);
+    // Floating windows hang off their workspace, after the tiled ones.
+    let floating = output_of("floating", &["-f", "json", "list", "all"]);
+    assert!(
+        floating.contains(r#","floating_nodes":[{"type":"floating_container","id":94000000012288,"name":null,"rect":{"x":300,"y":390,"width":400,"height":300},"focused":false,"workspace":"1","output":"eDP-1","nodes":[{"type":"window","id":94000000008192,"name":"Calculator","#),
+        "{}",
+        floating
+    );
+    assert!(output_of("floating", &["-f", "tsv", "list", "all"])
+        .contains("\nwindow\t94000000008192\tCalculator\t300\t390\t400\t300\ttrue\t1\teDP-1\n"));
+    // sway adds the app id and pid after the rest.
+    assert!(output_of("sway", &["-f", "json", "get", "right"])
+        .ends_with(r#""workspace":"1","output":"eDP-1","app_id":"firefox","pid":2202}"#));
+
+    let terminal = "window\t94000000000000\tTerminal\t0\t0\t640\t1080\tfalse\t1\teDP-1";
+    let firefox = "window\t94000000004096\tMozilla Firefox\t640\t0\t640\t1080\ttrue\t1\teDP-1";
+    let tsv = |args: &[&str]| output_of("three_columns", &[&["-f", "tsv"], args].concat());
+    assert_eq!(tsv(&["get", "left"]), terminal);
+    assert_eq!(tsv(&["list", "focused"]), firefox);
+    let windows = tsv(&["list", "windows"]);
+    assert_eq!(
+        windows.lines().take(2).collect::<Vec<_>>(),
+        [terminal, firefox]
+    );
+    let all = tsv(&["list", "all"]);
+    assert_eq!(
+        all.lines().next(),
+        Some("root\t94000000045056\troot\t0\t0\t1920\t1080\tfalse\t\t")
+    );
+    assert!(all.lines().all(|row| row.split('\t').count() == 10));
+    assert!(all.contains(&format!("\n{}\n", terminal)));
+}
+
+#[test]
+fn missing_neighbour_is_an_error() {
+    let mut backend = fixture("nested_splits");
+    let result = execute(
+        Command::Focus {
+            navigation: navigation(Target::Down),
+        },
+        OutputFormat::Text,
+        &mut backend,
+        &mut History::in_memory(),
+        &Config::default(),
+    );
+    assert_eq!(result.unwrap_err().exit_code(), 1);
+    assert!(backend.commands.is_empty());
+}
+
+#[test]
+fn sway_views_are_windows() {
+    let con = tree_of("sway");
+    let root = Node::root(&con);
+    let windows: Vec<String> = root
+        .get_windows()
+        .into_iter()
+        .filter_map(|window| window.current.name.clone())
+        .collect();
+    assert_eq!(windows, ["foot", "Mozilla Firefox", "Steam"]);
+
+    let foot = window(&root, "foot");
+    assert_eq!(foot.current.app_id.as_deref(), Some("foot"));
+    assert_eq!(foot.current.pid, Some(2101));
+    assert_eq!(name(root.get_focused()).as_deref(), Some("foot"));
+    assert_eq!(name(foot.next_window()).as_deref(), Some("Mozilla Firefox"));
+    assert_eq!(
+        name(window(&root, "Steam").previous_window()).as_deref(),
+        Some("Mozilla Firefox")
+    );
+}
+
+#[test]
+fn sway_neighbours_and_visibility() {
+    let mut backend = fixture("sway");
+    let con = tree(&mut backend);
+    let root = Node::root(&con);
+    let foot = window(&root, "foot");
+    assert_eq!(
+        name(foot.get_neighbour(Direction::Right)).as_deref(),
+        Some("Mozilla Firefox")
+    );
+    assert_eq!(
+        name(window(&root, "Steam").get_neighbour(Direction::Up)).as_deref(),
+        Some("Mozilla Firefox")
+    );
+
+    let workspaces = backend.get_workspaces().unwrap();
+    let outputs = backend.get_outputs().unwrap();
+    assert_eq!(root.get_visible_windows(&workspaces, &outputs).len(), 3);
+}
+
+#[test]
+fn grid_cells_and_neighbours() {
+    let grid = Grid {
+        rows: 2,
+        columns: 3,
+    };
+    assert_eq!(grid.cell(1, 5), Some((1, 1)));
+    assert_eq!(grid.cell(7, 5), None);
+    assert_eq!(grid.neighbour(1, 1, Direction::Right), Some(2));
+    assert_eq!(grid.neighbour(1, 1, Direction::Down), Some(4));
+    assert_eq!(grid.neighbour(1, 1, Direction::Left), None);
+    assert_eq!(grid.neighbour(1, 3, Direction::Right), None);
+    assert_eq!(grid.neighbour(7, 12, Direction::Up), Some(9));
+}
+
+#[test]
+fn workspace_switches_to_the_adjacent_cell() {
+    let mut backend = fixture("three_columns");
+    for action in [
+        WorkspaceAction::Right,
+        WorkspaceAction::Down,
+        WorkspaceAction::Move {
+            direction: Direction::Right,
+        },
+    ] {
+        execute(
+            Command::Workspace {
+                rows: None,
+                columns: None,
+                action,
+            },
+            OutputFormat::Text,
+            &mut backend,
+            &mut History::in_memory(),
+            &Config::default(),
+        )
+        .unwrap();
+    }
+    assert_eq!(
+        backend.commands,
+        [
+            "workspace number 2",
+            "workspace number 4",
+            "move container to workspace number 2; workspace number 2",
+        ]
+    );
+
+    let result = execute(
+        Command::Workspace {
+            rows: None,
+            columns: None,
+            action: WorkspaceAction::Up,
+        },
+        OutputFormat::Text,
+        &mut backend,
+        &mut History::in_memory(),
+        &Config::default(),
+    );
+    assert_eq!(result.unwrap_err().exit_code(), 1);
+}
+
+#[test]
+fn ipc_replies_must_answer_the_request() {
+    let path = std::env::temp_dir().join(format!("i4-test-ipc-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = [0u8; 14];
+        stream.read_exact(&mut request).unwrap();
+        // A GET_WORKSPACES reply to the GET_TREE request.
+        let mut reply = b"i3-ipc".to_vec();
+        reply.extend_from_slice(&2u32.to_le_bytes());
+        reply.extend_from_slice(&1u32.to_le_bytes());
+        reply.extend_from_slice(b"[]");
+        stream.write_all(&reply).unwrap();
+    });
+
+    let mut backend = IpcBackend::connect(path.to_str().unwrap()).unwrap();
+    let err = backend.get_tree().unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(err.exit_code(), 5);
+    assert!(
+        format!("{:?}", err).contains("Reply of type 1"),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn daemon_answers_forwarded_commands() {
+    let _colour = COLOUR.lock().unwrap();
+    let path = std::env::temp_dir().join(format!("i4-test-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+    std::thread::spawn(move || {
+        let state = Mutex::new(State {
+            backend: CachedBackend::new(Box::new(fixture("three_columns"))),
+            history: History::in_memory(),
+            config: Config::default(),
+        });
+        daemon::serve(listener, &state);
+    });
+
+    // A client that never sends anything only holds up the next one for a
+    // moment.
+    let _idle = std::os::unix::net::UnixStream::connect(&path).unwrap();
+
+    let args = |line: &str| line.split(' ').map(OsString::from).collect::<Vec<_>>();
+    let output = daemon::forward(&path, &args("get left --format tsv"))
+        .unwrap()
+        .unwrap();
+    assert!(output.starts_with("window\t"));
+    assert!(output.contains("\tTerminal\t"));
+    assert_eq!(
+        daemon::forward(&path, &args("focus right")).unwrap(),
+        Some(String::new())
+    );
+    let err = daemon::forward(&path, &args("focus up")).unwrap_err();
+    assert_eq!(err.exit_code(), 1);
+    assert_eq!(err.to_string(), "No up node");
+
+    std::fs::remove_file(&path).unwrap();
+    assert!(daemon::forward(&path, &args("focus left"))
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn history_orders_windows_by_recent_focus() {
+    let mut history = History::in_memory();
+    for (id, at) in [(1, 0), (2, 10), (3, 20), (2, 30)] {
+        history.record(id, at);
+    }
+    let all = |_| true;
+    assert_eq!(history.mru(0, 2, all), Some(2));
+    assert_eq!(history.mru(1, 2, all), Some(3));
+    assert_eq!(history.mru(2, 2, all), Some(1));
+    assert_eq!(history.mru(3, 2, all), None);
+    // Closed windows drop out.
+    assert_eq!(history.mru(1, 2, |id| id != 3), Some(1));
+}
+
+#[test]
+fn mru_cycle_commits_the_order_after_the_timeout() {
+    let mut history = History::in_memory();
+    for (id, at) in [(1, 0), (2, 10), (3, 20)] {
+        history.record(id, at);
+    }
+    let all = |_| true;
+    assert_eq!(history.cycle(3, all, 500, 100), Some(2));
+    // Focus changes made by the cycle itself don't reorder it.
+    history.record(2, 110);
+    assert_eq!(history.cycle(2, all, 500, 200), Some(1));
+    assert_eq!(history.cycle(1, all, 500, 300), Some(3));
+    assert_eq!(history.cycle(3, all, 500, 400), Some(2));
+
+    // After the timeout the window the cycle ended on is the most recent.
+    history.settle(1000);
+    assert_eq!(history.mru(1, 2, all), Some(3));
+    assert_eq!(history.cycle(2, all, 500, 1100), Some(3));
+}
+
+#[test]
+fn focus_back_returns_to_the_previous_window() {
+    let mut backend = fixture("three_columns");
+    let con = tree(&mut backend);
+    let root = Node::root(&con);
+    let terminal = window(&root, "Terminal").current.id;
+    let mut history = History::in_memory();
+    history.record(terminal, 0);
+
+    execute(
+        Command::Focus {
+            navigation: navigation(Target::Back),
+        },
+        OutputFormat::Text,
+        &mut backend,
+        &mut history,
+        &Config::default(),
+    )
+    .unwrap();
+    assert_eq!(backend.commands, [format!("[con_id={}] focus", terminal)]);
+}
+
+#[test]
+fn focus_mru_goes_n_windows_back() {
+    let mut backend = fixture("three_columns");
+    let con = tree(&mut backend);
+    let root = Node::root(&con);
+    let terminal = window(&root, "Terminal").current.id;
+    let code = window(&root, "notes.md - Code").current.id;
+    let mut history = History::in_memory();
+    history.record(terminal, 0);
+    history.record(code, 10);
+
+    let parse = |args: &[&str]| {
+        Cli::try_parse_from_with(std::iter::once(&"i4").chain(args), &Config::default())
+    };
+    let cli = parse(&["focus", "mru", "2"]).unwrap();
+    execute(
+        cli.command,
+        OutputFormat::Text,
+        &mut backend,
+        &mut history,
+        &Config::default(),
+    )
+    .unwrap();
+    assert_eq!(backend.commands, [format!("[con_id={}] focus", terminal)]);
+
+    // Only mru counts, and from one.
+    for args in [
+        &["focus", "mru", "0"][..],
+        &["focus", "right", "7"],
+        &["move", "back", "2"],
+    ] {
+        assert_eq!(parse(args).unwrap_err().exit_code(), 2, "{:?}", args);
+    }
+}
+
+#[test]
+fn workspace_blocks_follow_each_outputs_grid() {
+    let config = Config::parse(
+        "[grid]\nrows = 2\ncolumns = 2\n\n[grid.outputs.DP-1]\nrows = 1\ncolumns = 3\n",
+    )
+    .unwrap();
+    let mut backend = fixture("two_outputs");
+    let workspaces = r#"[
+        {"num": 1, "name": "1", "visible": true, "focused": false, "output": "DP-1"},
+        {"num": 5, "name": "5", "visible": true, "focused": true, "output": "HDMI-1"}
+    ]"#;
+    backend.set_workspaces(workspaces).unwrap();
+    let mut workspace = |action| {
+        execute(
+            Command::Workspace {
+                rows: None,
+                columns: None,
+                action,
+            },
+            OutputFormat::Text,
+            &mut backend,
+            &mut History::in_memory(),
+            &config,
+        )
+    };
+    // DP-1 holds 1 to 3, so HDMI-1's 2 x 2 grid is 4 5 / 6 7.
+    workspace(WorkspaceAction::Down).unwrap();
+    workspace(WorkspaceAction::Left).unwrap();
+    assert_eq!(
+        workspace(WorkspaceAction::Right).unwrap_err().exit_code(),
+        1
+    );
+    assert_eq!(
+        backend.commands,
+        ["workspace number 7", "workspace number 4"]
+    );
+}
+
+#[test]
+fn config_defaults_and_validation() {
+    let config = Config::default();
+    assert_eq!(config.scope, Scope::Workspace);
+    assert_eq!(
+        config.grid.for_output("eDP-1"),
+        Grid {
+            rows: 3,
+            columns: 3
+        }
+    );
+
+    let config =
+        Config::parse("wrap = \"global\"\nformat = \"json\"\n[colours]\nwindow = \"red\"\n")
+            .unwrap();
+    assert_eq!(config.wrap, Wrap::Global);
+    assert_eq!(config.format, OutputFormat::Json);
+    assert_eq!(config.colours.window, "red");
+    assert_eq!(config.colours.root, "bold,blue");
+
+    assert!(Config::parse("wrap = \"sideways\"").is_err());
+    assert!(Config::parse("colour = 1").is_err());
+    assert!(Config::parse("[grid]\nrows = 0").is_err());
+    assert!(Config::parse("[colours]\nwindow = \"purple\"")
+        .unwrap_err()
+        .contains("purple"));
+}
+
+#[test]
+fn config_sets_command_line_defaults() {
+    let config = Config::parse("scope = \"all\"\nwrap = \"output\"\nformat = \"tsv\"").unwrap();
+    let cli = Cli::try_parse_from_with(["i4", "focus", "next"], &config).unwrap();
+    assert_eq!(cli.format, OutputFormat::Tsv);
+    match cli.command {
+        Command::Focus { navigation } => {
+            assert_eq!(navigation.scope, Scope::All);
+            assert_eq!(navigation.wrap, Wrap::Output);
+        }
+        command => panic!("Parsed {:?}", command),
+    }
+
+    // Flags still win over the config.
+    let cli = Cli::try_parse_from_with(
+        ["i4", "-f", "text", "focus", "next", "--wrap", "none"],
+        &config,
+    )
+    .unwrap();
+    assert_eq!(cli.format, OutputFormat::Text);
+    match cli.command {
+        Command::Focus { navigation } => assert_eq!(navigation.wrap, Wrap::None),
+        command => panic!("Parsed {:?}", command),
+    }
+}
+
+#[test]
+fn log_filter_picks_the_longest_module_prefix() {
+    let filter = Filter::parse("error,i4::daemon=debug,i4::daemon::events=off");
+    assert!(filter.enabled(Level::Error, "i4"));
+    assert!(!filter.enabled(Level::Warn, "i4::backend"));
+    assert!(filter.enabled(Level::Debug, "i4::daemon"));
+    assert!(!filter.enabled(Level::Trace, "i4::daemon"));
+    assert!(!filter.enabled(Level::Error, "i4::daemon::events"));
+    // Prefixes only match whole path segments.
+    assert!(!filter.enabled(Level::Debug, "i4::daemonic"));
+
+    // A bare module lets everything from it through, junk is skipped.
+    let filter = Filter::parse("i4::grid,loud=shouting");
+    assert!(filter.enabled(Level::Trace, "i4::grid"));
+    assert!(filter.enabled(Level::Warn, "loud"));
+    assert!(!filter.enabled(Level::Info, "loud"));
+}
+
+#[test]
+fn verbosity_shifts_every_level() {
+    let mut filter = Filter::parse("");
+    assert!(filter.enabled(Level::Warn, "i4"));
+    assert!(!filter.enabled(Level::Info, "i4"));
+
+    filter.shift(2);
+    assert!(filter.enabled(Level::Debug, "i4"));
+    assert!(!filter.enabled(Level::Trace, "i4"));
+
+    let mut filter = Filter::parse("info,i4::daemon=trace");
+    filter.shift(-2);
+    assert!(filter.enabled(Level::Error, "i4"));
+    assert!(!filter.enabled(Level::Warn, "i4"));
+    assert!(filter.enabled(Level::Info, "i4::daemon"));
+
+    let cli = Cli::try_parse_from_with(["i4", "-vv", "list", "all"], &Config::default()).unwrap();
+    assert_eq!(cli.verbose, 2);
+    assert!(
+        Cli::try_parse_from_with(["i4", "-v", "-q", "list", "all"], &Config::default()).is_err()
+    );
+}
+
+#[test]
+fn log_timestamps_are_utc() {
+    assert_eq!(logger::timestamp(0), "1970-01-01T00:00:00.000Z");
+    assert_eq!(
+        logger::timestamp(951_825_600_123),
+        "2000-02-29T12:00:00.123Z"
+    );
+    assert_eq!(
+        logger::timestamp(1_767_225_599_999),
+        "2025-12-31T23:59:59.999Z"
+    );
+}
+
+#[test]
+fn log_file_rotates_by_size() {
+    let dir = std::env::temp_dir().join(format!("i4-test-log-{}", std::process::id()));
+    let path = dir.join("i4.log");
+    let rotation = Rotation {
+        max_size: 200,
+        keep: 2,
+    };
+    let logger = Logger::to_file(Filter::parse("info"), &path, rotation).unwrap();
+    for n in 0..15 {
+        logger.log(Level::Info, "i4", format_args!("message {}", n));
+    }
+    logger.log(Level::Debug, "i4", format_args!("filtered out"));
+
+    let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap_or_default();
+    let (current, first, second) = (read("i4.log"), read("i4.log.1"), read("i4.log.2"));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert!(current.trim_end().ends_with("INFO  i4: message 14"));
+    assert!(!current.contains("filtered out"));
+    for file in [&current, &first, &second] {
+        assert!(!file.is_empty() && file.len() <= 200);
+    }
+    assert!(!dir.join("i4.log.3").exists());
+    // Only the newest messages survive.
+    assert!(!(current.clone() + &first + &second).contains("message 0"));
+}
+
+#[test]
+fn nodes_are_logged_without_colour() {
+    let _colour = COLOUR.lock().unwrap();
+    let con = tree_of("three_columns");
+    let firefox = window(&Node::root(&con), "Mozilla Firefox");
+    macros::set_colour(true);
+    let (styled, plain) = (firefox.to_string(), format!("{:#}", firefox));
+    macros::set_colour(false);
+    assert!(styled.contains('\x1b'), "{:?}", styled);
+    assert!(plain.starts_with("[Window] "), "{:?}", plain);
+    assert!(!plain.contains('\x1b'), "{:?}", plain);
+}
+
+#[test]
+fn log_file_defaults_to_the_state_directory() {
+    let config = Config::parse("[log]\nkeep = 5").unwrap();
+    assert_eq!(config.log.file, None);
+    assert_eq!(config.log.rotation().keep, 5);
+    assert!(Config::parse("[log]\nmax_size = 0").is_err());
+    assert!(logger::default_path().is_some_and(|path| path.ends_with("i4/i4.log")));
+}
+
+/// Held by the tests that switch colour on or off, since the setting is
+/// global.
+static COLOUR: Mutex<()> = Mutex::new(());
+
+#[test]
+fn results_are_plain_when_not_on_a_terminal() {
+    let _colour = COLOUR.lock().unwrap();
+    // What the daemon does for a client whose stdout is a pipe.
+    macros::set_colour(false);
+    assert_eq!(style!("bold,red", "{}", 1), "1");
+    assert_eq!(NodeType::Window.to_string(), "Window");
+
+    let mut backend = fixture("two_outputs");
+    let output = execute(
+        Command::List {
+            target: ListTarget::All,
+            filter: None,
+        },
+        OutputFormat::Text,
+        &mut backend,
+        &mut History::in_memory(),
+        &Config::default(),
+    )
+    .unwrap();
+    assert!(!output.contains('\x1b'));
+}
+
+fn filtered(expression: &str) -> Vec<String> {
+    let con = tree_of("properties");
+    let root = Node::root(&con);
+    let query = Query::parse(expression).unwrap();
+    root.get_all_windows()
+        .into_iter()
+        .filter(|window| query.matches(window))
+        .filter_map(|window| window.current.name.clone())
+        .collect()
+}
+
+#[test]
+fn filters_match_window_properties() {
+    assert_eq!(filtered("class=firefox"), ["Mozilla Firefox"]);
+    assert_eq!(filtered("instance=alacritty"), ["Terminal"]);
+    assert_eq!(filtered("title~'^notes'"), ["notes.md - Code"]);
+    assert_eq!(filtered("marks=main"), ["Mozilla Firefox"]);
+    assert_eq!(filtered("marks~^t"), ["Terminal"]);
+    assert_eq!(filtered("floating"), ["Calculator"]);
+    assert_eq!(filtered("urgent=true"), ["Chat - Slack"]);
+    assert_eq!(filtered("focused"), ["Mozilla Firefox"]);
+    assert_eq!(filtered("output=HDMI-1 && !urgent"), ["notes.md - Code"]);
+}
+
+#[test]
+fn filters_combine_with_precedence() {
+    // A workspace matches by its number as well as its full name.
+    assert_eq!(filtered("workspace=2"), filtered("workspace='2: chat'"));
+    assert_eq!(
+        filtered("workspace=1 && !floating"),
+        ["Terminal", "Mozilla Firefox"]
+    );
+    assert_eq!(
+        filtered("class=Slack || class=Code && workspace=1"),
+        ["Chat - Slack"]
+    );
+    assert_eq!(
+        filtered("(class=Slack || class=Code) && workspace=2"),
+        ["notes.md - Code", "Chat - Slack"]
+    );
+    assert_eq!(
+        filtered("class!=firefox && workspace=1 && floating=false"),
+        ["Terminal"]
+    );
+
+    for broken in [
+        "",
+        "class",
+        "colour=red",
+        "class=",
+        "floating~x",
+        "(urgent",
+        "title~'('",
+        "a & b",
+    ] {
+        assert!(Query::parse(broken).is_err(), "{} parsed", broken);
+    }
+}
+
+#[test]
+fn filter_is_limited_to_window_lists() {
+    let mut backend = fixture("properties");
+    let output = execute(
+        Cli::try_parse_from_with(
+            [
+                "i4",
+                "-f",
+                "json",
+                "list",
+                "windows",
+                "--filter",
+                "workspace=1",
+            ],
+            &Config::default(),
+        )
+        .unwrap()
+        .command,
+        OutputFormat::Json,
+        &mut backend,
+        &mut History::in_memory(),
+        &Config::default(),
+    )
+    .unwrap();
+    let windows: serde_json::Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(windows.as_array().unwrap().len(), 3);
+    assert!(output.contains("Calculator"));
+
+    let err = Cli::try_parse_from_with(
+        ["i4", "list", "all", "--filter", "urgent"],
+        &Config::default(),
+    )
+    .unwrap_err();
+    assert_eq!(err.exit_code(), 2);
+    let err = Cli::try_parse_from_with(
+        ["i4", "list", "windows", "--filter", "class"],
+        &Config::default(),
+    )
+    .unwrap_err();
+    assert_eq!(err.exit_code(), 2);
+}
+
+#[test]
+fn snapshots_load_back_as_they_were_saved() {
+    for name in ["two_outputs", "tabbed", "properties", "sway"] {
+        let snapshot = backend::snapshot(&mut fixture(name)).unwrap();
+        let mut loaded = FixtureBackend::from_json(&snapshot.to_string()).unwrap();
+        assert_eq!(
+            backend::snapshot(&mut loaded).unwrap(),
+            snapshot,
+            "{}",
+            name
+        );
+
+        let (before, after) = (tree_of(name), tree(&mut loaded));
+        let before = render_nodes(OutputFormat::Tsv, &Node::root(&before).get_all_windows());
+        let after = render_nodes(OutputFormat::Tsv, &Node::root(&after).get_all_windows());
+        assert_eq!(before, after, "{}", name);
+    }
+}
+
+#[test]
+fn snapshot_save_writes_a_tree_file() {
+    let path = std::env::temp_dir().join(format!("i4-test-snapshot-{}.json", std::process::id()));
+    let output = snapshot::save(&mut fixture("offset_outputs"), &path, false).unwrap();
+    assert_eq!(output, "");
+    let mut loaded = FixtureBackend::load(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    let con = tree(&mut loaded);
+    let focused = Node::root(&con).get_focused().unwrap();
+    assert_eq!(
+        name(focused.get_neighbour(Direction::Right)).as_deref(),
+        Some("notes.md - Code")
+    );
+    // Fields i4 has no use for are kept.
+    let json = snapshot::save(&mut fixture("offset_outputs"), Path::new("-"), false).unwrap();
+    let saved: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let original = std::fs::read_to_string(fixture_path("offset_outputs")).unwrap();
+    let original: serde_json::Value = serde_json::from_str(&original).unwrap();
+    assert_eq!(saved["tree"], original);
+    assert_eq!(saved["tree"]["border"], "normal");
+
+    let cli = Cli::try_parse_from_with(
+        ["i4", "list", "all", "--tree-file", "a.json"],
+        &Config::default(),
+    )
+    .unwrap();
+    assert_eq!(cli.tree_file, Some(PathBuf::from("a.json")));
+}
+
+#[test]
+fn anonymized_snapshots_keep_the_layout() {
+    let json = snapshot::save(&mut fixture("properties"), Path::new("-"), true).unwrap();
+    for secret in [
+        "Firefox",
+        "firefox",
+        "Slack",
+        "notes.md",
+        "Alacritty",
+        "chat",
+    ] {
+        assert!(!json.contains(secret), "{} leaked", secret);
+    }
+
+    let mut anonymized = FixtureBackend::from_json(&json).unwrap();
+    let con = tree(&mut anonymized);
+    let root = Node::root(&con);
+    let original = tree_of("properties");
+    let before = Node::root(&original).get_all_windows();
+    let after = root.get_all_windows();
+    assert_eq!(before.len(), after.len());
+    for (before, after) in before.iter().zip(&after) {
+        assert_eq!(before.current.id, after.current.id);
+        assert_eq!(before.current.rect, after.current.rect);
+        assert_eq!(before.current.marks, after.current.marks);
+        assert_eq!(before.current.focused, after.current.focused);
+        assert_eq!(before.current.floating, after.current.floating);
+        // The title is the name, under one pseudonym.
+        let properties = after.current.window_properties.clone().unwrap();
+        assert_eq!(after.current.name, properties.title);
+    }
+    assert_eq!(name(root.get_focused()).as_deref(), Some("title-2"));
+
+    // The workspace keeps its number, and the replies agree with the tree.
+    let workspaces = anonymized.get_workspaces().unwrap().workspaces;
+    assert_eq!(workspaces[1].name, "2: workspace-1");
+    assert_eq!(workspaces[1].num, 2);
+    let outputs = anonymized.get_outputs().unwrap().outputs;
+    assert_eq!(
+        outputs[1].current_workspace.as_deref(),
+        Some("2: workspace-1")
+    );
+
+    // The same class gets the same pseudonym.
+    let json = snapshot::save(&mut fixture("nested_splits"), Path::new("-"), true).unwrap();
+    let con = tree(&mut FixtureBackend::from_json(&json).unwrap());
+    let classes: Vec<_> = Node::root(&con)
+        .get_windows()
+        .into_iter()
+        .map(|window| {
+            window
+                .current
+                .window_properties
+                .clone()
+                .unwrap()
+                .class
+                .unwrap()
+        })
+        .collect();
+    assert_eq!(classes, ["class-1", "class-2", "class-1", "class-3"]);
+}
+
+#[test]
+fn dry_run_prints_commands_instead_of_running_them() {
+    let mut backend = fixture("three_columns");
+    let code = window(&Node::root(&tree(&mut backend)), "notes.md - Code")
+        .current
+        .id;
+    let mut history = History::in_memory();
+    let dry_run = |args: &[&str], backend: &mut FixtureBackend, history: &mut History| {
+        let cli = Cli::try_parse_from_with(std::iter::once(&"i4").chain(args), &Config::default())
+            .unwrap();
+        execute_cli(cli, backend, history, &Config::default())
+    };
+
+    let output = dry_run(&["--dry-run", "focus", "right"], &mut backend, &mut history).unwrap();
+    assert_eq!(output, format!("[con_id={}] focus", code));
+    assert!(backend.commands.is_empty());
+    // The focus history doesn't hear of it either.
+    assert_eq!(
+        serde_json::to_string(&history).unwrap(),
+        r#"{"windows":[],"cycle":null}"#
+    );
+
+    let output = dry_run(
+        &["workspace", "--dry-run", "move", "down"],
+        &mut backend,
+        &mut history,
+    )
+    .unwrap();
+    assert_eq!(
+        output,
+        "move container to workspace number 4; workspace number 4"
+    );
+    assert!(backend.commands.is_empty());
+
+    // Queries still print their results.
+    let output = dry_run(&["--dry-run", "get", "left"], &mut backend, &mut history).unwrap();
+    assert!(output.contains("Terminal"));
+}
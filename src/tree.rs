@@ -0,0 +1,70 @@
+//! The layout tree as the window manager reports it.
+
+use crate::direction::Rect;
+use i3ipc::reply::{NodeLayout as I3NodeLayout, NodeType as I3NodeType};
+
+/// A container in the layout tree, as reported by i3 or sway.
+///
+/// This mirrors the parts of i3ipc's `reply::Node` that i4 needs, plus the
+/// fields i3ipc does not know about: `marks`, `floating` and the ones sway
+/// adds for Wayland views.
+#[derive(Clone, Debug)]
+pub struct Con {
+    /// i3's id for the container, as used in `[con_id=…]` criteria.
+    pub id: i64,
+    /// The window title, or the name of an output or workspace.
+    pub name: Option<String>,
+    /// What kind of container this is, in i3's terms.
+    pub nodetype: I3NodeType,
+    /// How the container arranges its children.
+    pub layout: I3NodeLayout,
+    /// Where the container is, in absolute display coordinates.
+    pub rect: Rect,
+    /// Whether this is the focused container.
+    pub focused: bool,
+    /// Child ids in focus order, most recently focused first.
+    pub focus: Vec<i64>,
+    /// The tiled children.
+    pub nodes: Vec<Con>,
+    /// The floating children, which only workspaces have.
+    pub floating_nodes: Vec<Con>,
+    /// The X11 window id, for X11 (and on sway, Xwayland) windows.
+    pub window: Option<i32>,
+    /// The X11 properties of an X11 window.
+    pub window_properties: Option<WindowProperties>,
+    /// Set on windows with the urgency hint, and on their ancestors up to
+    /// the workspace.
+    pub urgent: bool,
+    /// Whether the container floats, whether the user or i3 decided so.
+    pub floating: bool,
+    /// The marks set on the container with i3's `mark` command.
+    pub marks: Vec<String>,
+    /// sway: the Wayland app id of a native Wayland view.
+    pub app_id: Option<String>,
+    /// sway: the process id of the client owning the view.
+    pub pid: Option<i32>,
+    /// sway: the protocol the view uses, `xdg_shell` or `xwayland`.
+    pub shell: Option<String>,
+}
+
+/// The X11 properties of a window.
+#[derive(Clone, Debug, Default)]
+pub struct WindowProperties {
+    /// The class part of `WM_CLASS`.
+    pub class: Option<String>,
+    /// The instance part of `WM_CLASS`.
+    pub instance: Option<String>,
+    /// The window's own title, before i3's `title_format`.
+    pub title: Option<String>,
+}
+
+impl Con {
+    /// Whether this container holds a client window rather than other
+    /// containers. Wayland views on sway have no X11 window id, so sway's
+    /// view fields count too. sway gives floating views the `floating_con`
+    /// type that i3 uses for the container around a floating window.
+    pub fn is_window(&self) -> bool {
+        (self.nodetype == I3NodeType::Con || self.nodetype == I3NodeType::FloatingCon)
+            && (self.window.is_some() || self.app_id.is_some() || self.shell.is_some())
+    }
+}
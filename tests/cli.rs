@@ -0,0 +1,195 @@
+//! Whole `i4` invocations against a mock i3: parsing, config, logging, the
+//! focus history and the daemon included.
+
+mod common;
+
+use common::{fixture_path, stdout, MockI3};
+use i4::direction::Direction;
+use i4::tree::Con;
+use i4::Node;
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+
+fn window<'a>(root: &Node<'a>, name: &str) -> Node<'a> {
+    root.get_all_windows()
+        .into_iter()
+        .find(|window| window.con().name.as_deref() == Some(name))
+        .unwrap_or_else(|| panic!("No window named {:?}", name))
+}
+
+fn id(tree: &Con, name: &str) -> i64 {
+    window(&Node::root(tree), name).con().id
+}
+
+fn focused(mock: &MockI3) -> Option<String> {
+    let tree = mock.tree();
+    Node::root(&tree)
+        .get_focused()
+        .and_then(|node| node.con().name.clone())
+}
+
+#[test]
+fn focus_runs_against_a_mock_i3() {
+    let mock = MockI3::start("three_columns").unwrap().mutating();
+    let code = id(&mock.tree(), "notes.md - Code");
+    let firefox = id(&mock.tree(), "Mozilla Firefox");
+
+    let output = mock.run(&["focus", "right"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "");
+    assert_eq!(mock.commands(), [format!("[con_id={}] focus", code)]);
+    assert_eq!(focused(&mock).as_deref(), Some("notes.md - Code"));
+
+    // The next invocation starts from where the last one left the focus.
+    let output = mock.run(&["focus", "right"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(mock.commands().len(), 1);
+
+    // ... and from the history the last one saved.
+    assert!(mock.run(&["focus", "back"]).status.success());
+    assert_eq!(mock.commands()[1], format!("[con_id={}] focus", firefox));
+    assert_eq!(focused(&mock).as_deref(), Some("Mozilla Firefox"));
+}
+
+#[test]
+fn focus_back_returns_to_a_floating_window() {
+    let mock = MockI3::start("floating").unwrap().mutating();
+    let calculator = id(&mock.tree(), "Calculator");
+
+    assert!(mock.run(&["focus", "right"]).status.success());
+    assert_eq!(focused(&mock).as_deref(), Some("Mozilla Firefox"));
+    let output = mock.run(&["focus", "back"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(mock.commands()[1], format!("[con_id={}] focus", calculator));
+    assert_eq!(focused(&mock).as_deref(), Some("Calculator"));
+}
+
+#[test]
+fn move_swaps_windows_in_a_mock_i3() {
+    let mock = MockI3::start("nested_splits").unwrap().mutating();
+    let htop = id(&mock.tree(), "htop");
+    let terminal = id(&mock.tree(), "Terminal");
+
+    assert!(mock.run(&["move", "left"]).status.success());
+    assert_eq!(
+        mock.commands(),
+        [format!(
+            "[con_id={}] swap container with con_id {}",
+            htop, terminal
+        )]
+    );
+
+    let tree = mock.tree();
+    let root = Node::root(&tree);
+    let names: Vec<_> = root
+        .get_windows()
+        .into_iter()
+        .filter_map(|w| w.con().name.clone())
+        .collect();
+    assert_eq!(names, ["htop", "Mozilla Firefox", "Terminal", "Calculator"]);
+    // htop took over Terminal's place and kept the focus.
+    let htop = window(&root, "htop");
+    assert_eq!(htop.con().rect, (0, 0, 960, 1080));
+    assert!(htop.con().focused);
+    let right = window(&root, "Terminal").get_neighbour(Direction::Right);
+    assert_eq!(
+        right.and_then(|node| node.con().name.clone()).as_deref(),
+        Some("Calculator")
+    );
+}
+
+#[test]
+fn mock_i3_answers_every_query() {
+    let mock = MockI3::start("two_outputs").unwrap();
+
+    let output = mock.run(&["list", "visible"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output).lines().count(), 3);
+    assert!(mock.run(&["workspace", "move", "right"]).status.success());
+    assert_eq!(
+        mock.commands(),
+        ["move container to workspace number 2", "workspace number 2"]
+    );
+    // Without `mutating` the layout stays as it was.
+    assert_eq!(focused(&mock).as_deref(), Some("Mozilla Firefox"));
+}
+
+#[test]
+fn snapshots_keep_what_i3_sent() {
+    let mock = MockI3::start("offset_outputs").unwrap();
+    let fixture = std::fs::read_to_string(fixture_path("offset_outputs")).unwrap();
+    let fixture: serde_json::Value = serde_json::from_str(&fixture).unwrap();
+
+    let output = mock.run(&["snapshot", "save", "-"]);
+    assert!(output.status.success(), "{:?}", output);
+    let snapshot: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(snapshot["tree"], fixture);
+
+    // An anonymized one only has the fields i4 reads.
+    let output = mock.run(&["snapshot", "save", "--anonymize", "-"]);
+    let snapshot: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert!(fixture.get("border").is_some());
+    assert!(snapshot["tree"].get("border").is_none());
+}
+
+#[test]
+fn commands_go_through_a_running_daemon() {
+    let mock = MockI3::start("three_columns").unwrap().mutating();
+    let code = id(&mock.tree(), "notes.md - Code");
+    let _daemon = mock.daemon();
+
+    // Only the daemon can reach i3 now, so an answer has to come from it.
+    let output = mock
+        .command(&["focus", "right"])
+        .env("I3SOCK", "/nonexistent/i3.sock")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(mock.commands(), [format!("[con_id={}] focus", code)]);
+
+    let output = mock
+        .command(&["get", "left"])
+        .env("I3SOCK", "/nonexistent/i3.sock")
+        .output()
+        .unwrap();
+    assert!(stdout(&output).contains("Mozilla Firefox"));
+
+    // Arguments reach the daemon as they were, UTF-8 or not.
+    let output = mock
+        .command(&["get", "left", "--log-file"])
+        .arg(OsStr::from_bytes(b"/nonexistent/\xff.log"))
+        .env("I3SOCK", "/nonexistent/i3.sock")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).contains("Mozilla Firefox"));
+}
+
+#[test]
+fn tree_files_print_the_commands_they_would_run() {
+    let mock = MockI3::start("three_columns").unwrap();
+    let terminal = id(&mock.tree(), "Terminal");
+    let file = fixture_path("three_columns");
+
+    let output = mock
+        .command(&["focus", "left", "--tree-file"])
+        .arg(&file)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(stdout(&output), format!("[con_id={}] focus", terminal));
+    // The snapshot stands in for i3, so the mock hears nothing.
+    assert!(mock.commands().is_empty());
+}
+
+#[test]
+fn connection_errors_have_their_exit_code() {
+    let mock = MockI3::start("three_columns").unwrap();
+    let output = mock
+        .command(&["focus", "right"])
+        .env("I3SOCK", "/nonexistent/i3.sock")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(4));
+    assert!(mock.commands().is_empty());
+}
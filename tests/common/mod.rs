@@ -0,0 +1,387 @@
+//! A mock i3 for the tests that run the `i4` binary, and the helpers to run
+//! it against one.
+
+use i4::backend::{self, Backend, FixtureBackend};
+use i4::tree::Con;
+use serde_json::{json, Value};
+use std::io::{self, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const MAGIC: &[u8; 6] = b"i3-ipc";
+const RUN_COMMAND: u32 = 0;
+const GET_WORKSPACES: u32 = 1;
+const SUBSCRIBE: u32 = 2;
+const GET_OUTPUTS: u32 = 3;
+const GET_TREE: u32 = 4;
+
+/// A fake i3 listening on a Unix socket in a temporary directory of its
+/// own, so that tests can run i4 against its real IPC code.
+///
+/// It serves a layout loaded from JSON, in the same formats `FixtureBackend`
+/// takes, and records every command it gets. With `mutating` it also
+/// carries out `focus` and `swap` commands on the layout, so that a sequence
+/// of invocations sees their effects.
+///
+/// The directory also holds the config, state and runtime directories of
+/// the i4 processes `i4` starts, and goes away with the mock.
+pub struct MockI3 {
+    dir: PathBuf,
+    path: PathBuf,
+    state: Arc<Mutex<State>>,
+}
+
+struct State {
+    tree: Value,
+    workspaces: Option<Value>,
+    outputs: Option<Value>,
+    commands: Vec<String>,
+    mutating: bool,
+}
+
+impl MockI3 {
+    /// Serves the layout in `fixture` on a fresh socket.
+    pub fn start(name: &str) -> Result<Self, String> {
+        let fixture = fixture_path(name);
+        let json = std::fs::read_to_string(&fixture)
+            .map_err(|err| format!("{}: {}", fixture.display(), err))?;
+        let value: Value = serde_json::from_str(&json).map_err(|err| err.to_string())?;
+        let state = match value.get("tree") {
+            Some(tree) => State {
+                tree: tree.clone(),
+                workspaces: value.get("workspaces").cloned(),
+                outputs: value.get("outputs").cloned(),
+                commands: Vec::new(),
+                mutating: false,
+            },
+            None => State {
+                tree: value,
+                workspaces: None,
+                outputs: None,
+                commands: Vec::new(),
+                mutating: false,
+            },
+        };
+
+        // Unique per mock, since the tests of a run share the process and
+        // may well start several from the same fixture.
+        static MOCKS: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "i4-mock-{}-{}",
+            std::process::id(),
+            MOCKS.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+        let path = dir.join("i3.sock");
+        let listener = UnixListener::bind(&path).map_err(|err| err.to_string())?;
+
+        let state = Arc::new(Mutex::new(state));
+        let server_state = Arc::clone(&state);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let state = Arc::clone(&server_state);
+                thread::spawn(move || serve(stream, &state));
+            }
+        });
+        Ok(MockI3 { dir, path, state })
+    }
+
+    /// Makes `focus` and `swap` commands change the layout.
+    pub fn mutating(self) -> Self {
+        self.state.lock().unwrap().mutating = true;
+        self
+    }
+
+    /// Every command received so far, one per `;` separated part.
+    pub fn commands(&self) -> Vec<String> {
+        self.state.lock().unwrap().commands.clone()
+    }
+
+    /// The layout as it is now.
+    pub fn tree(&self) -> Con {
+        let tree = self.state.lock().unwrap().tree.to_string();
+        FixtureBackend::from_json(&tree)
+            .and_then(|mut fixture| fixture.get_tree().map_err(|err| err.to_string()))
+            .unwrap()
+    }
+
+    /// An `i4` process set up to talk to this mock, and to keep its config,
+    /// history and daemon socket to itself.
+    pub fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_i4"));
+        command
+            .args(args)
+            .env("I3SOCK", &self.path)
+            .env("XDG_RUNTIME_DIR", &self.dir)
+            .env("XDG_STATE_HOME", self.dir.join("state"))
+            .env("XDG_CONFIG_HOME", self.dir.join("config"))
+            .env("NO_COLOR", "1")
+            .env_remove("SWAYSOCK")
+            .env_remove("I4_CONFIG")
+            .env_remove("I4_SOCKET")
+            .env_remove("RUST_LOG");
+        command
+    }
+
+    /// Runs `i4` with `args` to the end.
+    pub fn run(&self, args: &[&str]) -> Output {
+        self.command(args).output().unwrap()
+    }
+
+    /// Starts `i4 daemon` and waits until it listens.
+    pub fn daemon(&self) -> Daemon {
+        let child = self.command(&["daemon"]).spawn().unwrap();
+        let socket = self.dir.join("i4.sock");
+        let start = Instant::now();
+        while !socket.exists() {
+            assert!(
+                start.elapsed() < Duration::from_secs(10),
+                "The daemon did not start"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+        Daemon(child)
+    }
+}
+
+impl Drop for MockI3 {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// A running `i4 daemon`, stopped when dropped.
+pub struct Daemon(Child);
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+pub fn fixture_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(format!("{}.json", name))
+}
+
+/// What a finished `i4` printed to stdout.
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout)
+        .trim_end()
+        .to_string()
+}
+
+/// The `workspaces` and `outputs` replies i3 would send for `tree`.
+fn derive(tree: &Value) -> Value {
+    let mut fixture = FixtureBackend::from_json(&tree.to_string()).unwrap();
+    backend::snapshot(&mut fixture).unwrap()
+}
+
+/// Answers one client until it hangs up.
+fn serve(mut stream: UnixStream, state: &Mutex<State>) {
+    while let Ok((message_type, payload)) = receive(&mut stream) {
+        let reply = {
+            let mut state = state.lock().unwrap();
+            match message_type {
+                RUN_COMMAND => state.run(&payload),
+                GET_WORKSPACES => state
+                    .workspaces
+                    .clone()
+                    .unwrap_or_else(|| derive(&state.tree)["workspaces"].take()),
+                SUBSCRIBE => json!({ "success": true }),
+                GET_OUTPUTS => state
+                    .outputs
+                    .clone()
+                    .unwrap_or_else(|| derive(&state.tree)["outputs"].take()),
+                GET_TREE => state.tree.clone(),
+                _ => json!({ "success": false, "error": "Unsupported message type" }),
+            }
+        };
+        if send(&mut stream, message_type, &reply.to_string()).is_err() {
+            break;
+        }
+    }
+}
+
+fn receive(stream: &mut UnixStream) -> io::Result<(u32, String)> {
+    let mut header = [0u8; 14];
+    stream.read_exact(&mut header)?;
+    if &header[..6] != MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Bad magic"));
+    }
+    let length = u32::from_le_bytes([header[6], header[7], header[8], header[9]]) as usize;
+    let message_type = u32::from_le_bytes([header[10], header[11], header[12], header[13]]);
+    let mut payload = vec![0u8; length];
+    stream.read_exact(&mut payload)?;
+    Ok((message_type, String::from_utf8_lossy(&payload).into_owned()))
+}
+
+fn send(stream: &mut UnixStream, message_type: u32, payload: &str) -> io::Result<()> {
+    let mut message = Vec::with_capacity(14 + payload.len());
+    message.extend_from_slice(MAGIC);
+    message.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    message.extend_from_slice(&message_type.to_le_bytes());
+    message.extend_from_slice(payload.as_bytes());
+    stream.write_all(&message)
+}
+
+impl State {
+    /// Records the commands and replies with one outcome each, like i3.
+    fn run(&mut self, payload: &str) -> Value {
+        let outcomes = payload
+            .split(';')
+            .map(str::trim)
+            .filter(|command| !command.is_empty())
+            .map(|command| {
+                self.commands.push(command.to_string());
+                match self.mutate(command) {
+                    Ok(()) => json!({ "success": true }),
+                    Err(err) => json!({ "success": false, "error": err }),
+                }
+            })
+            .collect();
+        Value::Array(outcomes)
+    }
+
+    fn mutate(&mut self, command: &str) -> Result<(), String> {
+        if !self.mutating {
+            return Ok(());
+        }
+        let con_id = |text: &str| -> Result<i64, String> {
+            text.trim()
+                .parse()
+                .map_err(|_| format!("Expected a con_id, got `{}`", text))
+        };
+        let (criteria, action) = match command.strip_prefix("[con_id=") {
+            Some(rest) => rest.split_once(']').ok_or("Unclosed criteria")?,
+            None => return Ok(()),
+        };
+        let id = con_id(criteria)?;
+        let action = action.trim();
+        if pointer(&self.tree, id, String::new()).is_none() {
+            return Err("No window matches the criteria".to_string());
+        }
+        if action == "focus" {
+            focus(&mut self.tree, id);
+        } else if let Some(other) = action.strip_prefix("swap container with con_id") {
+            swap(&mut self.tree, id, con_id(other)?)?;
+        }
+        Ok(())
+    }
+}
+
+fn children_mut(node: &mut Value) -> impl Iterator<Item = &mut Value> {
+    let (nodes, floating_nodes) = match node {
+        Value::Object(map) => {
+            let mut nodes = None;
+            let mut floating_nodes = None;
+            for (key, value) in map.iter_mut() {
+                match key.as_str() {
+                    "nodes" => nodes = value.as_array_mut(),
+                    "floating_nodes" => floating_nodes = value.as_array_mut(),
+                    _ => {}
+                }
+            }
+            (nodes, floating_nodes)
+        }
+        _ => (None, None),
+    };
+    nodes
+        .into_iter()
+        .flatten()
+        .chain(floating_nodes.into_iter().flatten())
+}
+
+fn id(node: &Value) -> Option<i64> {
+    node.get("id").and_then(Value::as_i64)
+}
+
+/// Focuses the container `target` the way i3 does: it becomes the only
+/// focused one, and every ancestor puts the child leading to it first in
+/// its focus order. Returns whether `target` is in this subtree.
+fn focus(node: &mut Value, target: i64) -> bool {
+    let is_target = id(node) == Some(target);
+    node["focused"] = Value::Bool(is_target);
+    let mut path = None;
+    for child in children_mut(node) {
+        if focus(child, target) {
+            path = id(child);
+        }
+    }
+    if let (Some(child), Some(order)) = (path, node.get_mut("focus").and_then(Value::as_array_mut))
+    {
+        order.retain(|id| id.as_i64() != Some(child));
+        order.insert(0, json!(child));
+    }
+    is_target || path.is_some()
+}
+
+/// The JSON pointer to the container `target`.
+fn pointer(node: &Value, target: i64, prefix: String) -> Option<String> {
+    if id(node) == Some(target) {
+        return Some(prefix);
+    }
+    ["nodes", "floating_nodes"].iter().find_map(|key| {
+        node.get(key)?
+            .as_array()?
+            .iter()
+            .enumerate()
+            .find_map(|(i, child)| pointer(child, target, format!("{}/{}/{}", prefix, key, i)))
+    })
+}
+
+/// Swaps two containers, each taking over the other's place and size.
+fn swap(tree: &mut Value, a: i64, b: i64) -> Result<(), String> {
+    let missing = |id: i64| format!("No container with con_id {}", id);
+    let first = pointer(tree, a, String::new()).ok_or_else(|| missing(a))?;
+    let second = pointer(tree, b, String::new()).ok_or_else(|| missing(b))?;
+    let mut first_node = tree.pointer(&first).cloned().unwrap();
+    let mut second_node = tree.pointer(&second).cloned().unwrap();
+    std::mem::swap(&mut first_node["rect"], &mut second_node["rect"]);
+    *tree.pointer_mut(&first).unwrap() = second_node;
+    *tree.pointer_mut(&second).unwrap() = first_node;
+
+    // The parents' focus orders name their children, which just traded
+    // places, unless both have the same parent.
+    let parent = |pointer: &str| pointer.rsplitn(3, '/').nth(2).map(String::from);
+    if parent(&first) != parent(&second) {
+        swap_focus_ids(tree, a, b);
+    }
+    // The focus stays on the same window, wherever it went.
+    if let Some(focused) = focused(tree) {
+        focus(tree, focused);
+    }
+    Ok(())
+}
+
+fn focused(node: &Value) -> Option<i64> {
+    if node.get("focused").and_then(Value::as_bool) == Some(true) {
+        return id(node);
+    }
+    ["nodes", "floating_nodes"]
+        .iter()
+        .find_map(|key| node.get(key)?.as_array()?.iter().find_map(focused))
+}
+
+fn swap_focus_ids(node: &mut Value, a: i64, b: i64) {
+    if let Some(order) = node.get_mut("focus").and_then(Value::as_array_mut) {
+        for id in order.iter_mut() {
+            match id.as_i64() {
+                Some(id_) if id_ == a => *id = json!(b),
+                Some(id_) if id_ == b => *id = json!(a),
+                _ => {}
+            }
+        }
+    }
+    for child in children_mut(node) {
+        swap_focus_ids(child, a, b);
+    }
+}